@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE message_description_filters (
+    message_id TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    is_exclude INTEGER NOT NULL,
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+INSERT INTO message_description_filters(message_id, pattern, is_exclude)
+SELECT message_id, description_regex_filter, 0 FROM messages
+WHERE description_regex_filter IS NOT NULL AND description_regex_filter != '';
+
+ALTER TABLE messages
+ADD filter_case_sensitive INTEGER;
+
+-- filters created before this were matched case sensitively
+UPDATE messages SET filter_case_sensitive = 1
+WHERE description_regex_filter IS NOT NULL AND description_regex_filter != '';
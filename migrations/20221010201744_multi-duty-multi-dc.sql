@@ -0,0 +1,21 @@
+-- Add migration script here
+-- messages.data_center and messages.duty_name are kept as display labels, these tables are what boards match on
+CREATE TABLE message_duties (
+    message_id TEXT NOT NULL,
+    duty_name TEXT NOT NULL,
+    PRIMARY KEY (message_id, duty_name),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE message_data_centers (
+    message_id TEXT NOT NULL,
+    data_center TEXT NOT NULL,
+    PRIMARY KEY (message_id, data_center),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+INSERT INTO message_duties(message_id, duty_name)
+SELECT message_id, duty_name FROM messages;
+
+INSERT INTO message_data_centers(message_id, data_center)
+SELECT message_id, data_center FROM messages;
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE message_categories (
+    message_id TEXT NOT NULL,
+    category TEXT NOT NULL,
+    PRIMARY KEY (message_id, category),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE guild_blocked_authors (
+    guild_id TEXT NOT NULL,
+    character_name TEXT NOT NULL,
+    world TEXT NOT NULL,
+    PRIMARY KEY (guild_id, character_name, world),
+  	FOREIGN KEY (guild_id) REFERENCES guilds (guild_id)
+);
+
+CREATE TABLE guild_blocked_keywords (
+    guild_id TEXT NOT NULL,
+    keyword TEXT NOT NULL,
+    is_regex INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, keyword, is_regex),
+  	FOREIGN KEY (guild_id) REFERENCES guilds (guild_id)
+);
@@ -0,0 +1,5 @@
+-- Add migration script here
+ALTER TABLE messages
+ADD party_type TEXT;
+ALTER TABLE messages
+ADD min_prog_phase INTEGER;
@@ -0,0 +1,12 @@
+-- Add migration script here
+ALTER TABLE messages
+ADD overflow TEXT;
+ALTER TABLE messages
+ADD page INTEGER;
+CREATE TABLE message_overflow (
+    message_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    overflow_message_id TEXT NOT NULL,
+    PRIMARY KEY (message_id, position),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
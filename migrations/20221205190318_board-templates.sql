@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE message_templates (
+    message_id TEXT PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    footer TEXT NOT NULL,
+    color TEXT NOT NULL,
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
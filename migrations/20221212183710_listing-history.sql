@@ -0,0 +1,35 @@
+-- Add migration script here
+CREATE TABLE listing_history (
+    listing_id INTEGER PRIMARY KEY NOT NULL,
+    duty_name TEXT NOT NULL,
+    data_center TEXT NOT NULL,
+    pf_category TEXT NOT NULL,
+    character_name TEXT NOT NULL,
+    world TEXT NOT NULL,
+    flags TEXT NOT NULL,
+    slot_count INTEGER NOT NULL,
+    filled_count INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    first_seen_at INTEGER NOT NULL,
+    last_seen_at INTEGER NOT NULL,
+    last_expires_at INTEGER NOT NULL,
+    outcome TEXT,
+    ended_at INTEGER
+);
+CREATE INDEX listing_history_ended_at ON listing_history (ended_at);
+CREATE INDEX listing_history_duty_name ON listing_history (duty_name, first_seen_at);
+CREATE TABLE listing_fill_events (
+    listing_id INTEGER NOT NULL,
+    seen_at INTEGER NOT NULL,
+    filled_count INTEGER NOT NULL,
+    slot_count INTEGER NOT NULL,
+    PRIMARY KEY (listing_id, seen_at),
+  	FOREIGN KEY (listing_id) REFERENCES listing_history (listing_id) ON DELETE CASCADE
+);
+CREATE TABLE listing_descriptions (
+    listing_id INTEGER NOT NULL,
+    seen_at INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    PRIMARY KEY (listing_id, seen_at),
+  	FOREIGN KEY (listing_id) REFERENCES listing_history (listing_id) ON DELETE CASCADE
+);
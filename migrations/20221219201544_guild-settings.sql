@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE guild_settings (
+    guild_id TEXT PRIMARY KEY NOT NULL,
+    data_center TEXT,
+    locale TEXT,
+    timezone TEXT,
+    manager_role_id TEXT,
+    layout TEXT,
+    allow_statics INTEGER,
+    include_filter TEXT,
+    exclude_filter TEXT,
+  	FOREIGN KEY (guild_id) REFERENCES guilds (guild_id) ON DELETE CASCADE
+);
+INSERT INTO guild_settings(guild_id, locale) SELECT guild_id, locale FROM guilds WHERE guild_id IS NOT NULL AND locale IS NOT NULL;
+ALTER TABLE guilds
+DROP COLUMN locale;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE messages ADD COLUMN payload_hash TEXT;
+ALTER TABLE messages ADD COLUMN payload_edited_at INTEGER;
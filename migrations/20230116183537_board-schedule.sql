@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE messages ADD COLUMN refresh_minutes INTEGER;
+ALTER TABLE messages ADD COLUMN next_refresh_at INTEGER;
@@ -0,0 +1,141 @@
+-- Add migration script here
+-- The schema the SQLite migrations add up to as of 20221219201544, with BIGINT for SQLite's INTEGER
+-- and an id on list tables to keep the order they were given in, which SQLite gets from rowid.
+CREATE TABLE guilds (
+    guild_id TEXT PRIMARY KEY,
+    guild_name TEXT NOT NULL
+);
+
+CREATE TABLE messages (
+    message_id TEXT PRIMARY KEY NOT NULL,
+    channel_id TEXT NOT NULL,
+    data_center TEXT NOT NULL,
+    guild_id TEXT NOT NULL,
+    duty_name TEXT NOT NULL,
+    allow_statics BIGINT,
+    is_news BIGINT,
+    filter_case_sensitive BIGINT,
+    sort_mode TEXT,
+    party_type TEXT,
+    min_prog_phase BIGINT,
+    layout TEXT,
+    overflow TEXT,
+    page BIGINT,
+  	FOREIGN KEY (guild_id) REFERENCES guilds (guild_id)
+);
+
+CREATE TABLE message_description_filters (
+    id BIGSERIAL PRIMARY KEY,
+    message_id TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    is_exclude BIGINT NOT NULL,
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE message_duties (
+    id BIGSERIAL NOT NULL,
+    message_id TEXT NOT NULL,
+    duty_name TEXT NOT NULL,
+    PRIMARY KEY (message_id, duty_name),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE message_data_centers (
+    id BIGSERIAL NOT NULL,
+    message_id TEXT NOT NULL,
+    data_center TEXT NOT NULL,
+    PRIMARY KEY (message_id, data_center),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE message_categories (
+    id BIGSERIAL NOT NULL,
+    message_id TEXT NOT NULL,
+    category TEXT NOT NULL,
+    PRIMARY KEY (message_id, category),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE guild_blocked_authors (
+    guild_id TEXT NOT NULL,
+    character_name TEXT NOT NULL,
+    world TEXT NOT NULL,
+    PRIMARY KEY (guild_id, character_name, world),
+  	FOREIGN KEY (guild_id) REFERENCES guilds (guild_id)
+);
+
+CREATE TABLE guild_blocked_keywords (
+    guild_id TEXT NOT NULL,
+    keyword TEXT NOT NULL,
+    is_regex BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, keyword, is_regex),
+  	FOREIGN KEY (guild_id) REFERENCES guilds (guild_id)
+);
+
+CREATE TABLE message_overflow (
+    message_id TEXT NOT NULL,
+    position BIGINT NOT NULL,
+    overflow_message_id TEXT NOT NULL,
+    PRIMARY KEY (message_id, position),
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE message_templates (
+    message_id TEXT PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    footer TEXT NOT NULL,
+    color TEXT NOT NULL,
+  	FOREIGN KEY (message_id) REFERENCES messages (message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE listing_history (
+    listing_id BIGINT PRIMARY KEY NOT NULL,
+    duty_name TEXT NOT NULL,
+    data_center TEXT NOT NULL,
+    pf_category TEXT NOT NULL,
+    character_name TEXT NOT NULL,
+    world TEXT NOT NULL,
+    flags TEXT NOT NULL,
+    slot_count BIGINT NOT NULL,
+    filled_count BIGINT NOT NULL,
+    description TEXT NOT NULL,
+    first_seen_at BIGINT NOT NULL,
+    last_seen_at BIGINT NOT NULL,
+    last_expires_at BIGINT NOT NULL,
+    outcome TEXT,
+    ended_at BIGINT
+);
+
+CREATE INDEX listing_history_ended_at ON listing_history (ended_at);
+CREATE INDEX listing_history_duty_name ON listing_history (duty_name, first_seen_at);
+
+CREATE TABLE listing_fill_events (
+    listing_id BIGINT NOT NULL,
+    seen_at BIGINT NOT NULL,
+    filled_count BIGINT NOT NULL,
+    slot_count BIGINT NOT NULL,
+    PRIMARY KEY (listing_id, seen_at),
+  	FOREIGN KEY (listing_id) REFERENCES listing_history (listing_id) ON DELETE CASCADE
+);
+
+CREATE TABLE listing_descriptions (
+    listing_id BIGINT NOT NULL,
+    seen_at BIGINT NOT NULL,
+    description TEXT NOT NULL,
+    PRIMARY KEY (listing_id, seen_at),
+  	FOREIGN KEY (listing_id) REFERENCES listing_history (listing_id) ON DELETE CASCADE
+);
+
+CREATE TABLE guild_settings (
+    guild_id TEXT PRIMARY KEY NOT NULL,
+    data_center TEXT,
+    locale TEXT,
+    timezone TEXT,
+    manager_role_id TEXT,
+    layout TEXT,
+    allow_statics BIGINT,
+    include_filter TEXT,
+    exclude_filter TEXT,
+  	FOREIGN KEY (guild_id) REFERENCES guilds (guild_id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE messages ADD COLUMN error_count BIGINT;
+ALTER TABLE messages ADD COLUMN last_error TEXT;
+ALTER TABLE messages ADD COLUMN last_error_at BIGINT;
+ALTER TABLE messages ADD COLUMN quarantined_at BIGINT;
+ALTER TABLE messages ADD COLUMN recreate_if_deleted BIGINT;
+ALTER TABLE guild_settings ADD COLUMN log_channel_id TEXT;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE messages ADD COLUMN refresh_minutes BIGINT;
+ALTER TABLE messages ADD COLUMN next_refresh_at BIGINT;
@@ -0,0 +1,41 @@
+use crate::filter_util;
+use crate::scraper_util;
+use crate::xiv_util::PFListing;
+
+pub const MAX_BLOCKED_AUTHORS: usize = 200;
+pub const MAX_BLOCKED_KEYWORDS: usize = 50;
+
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GuildBlocklist {
+    authors: Vec<(String, String)>, // lowercase (character name, world)
+    keywords: filter_util::DescriptionFilter
+}
+
+impl GuildBlocklist {
+    // keywords are (keyword or pattern, is_regex)
+    pub fn new(authors: &[(String, String)], keywords: &[(String, bool)]) -> GuildBlocklist {
+        let patterns = keywords.iter().map(|(keyword, is_regex)| get_keyword_pattern(keyword, *is_regex)).collect::<Vec<String>>();
+        GuildBlocklist {
+            authors: authors.iter().map(|(name, world)| (name.to_lowercase(), world.to_lowercase())).collect(),
+            keywords: filter_util::DescriptionFilter::new_lossy(&[], &patterns, false)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.authors.is_empty() && self.keywords.is_empty()
+    }
+
+    pub fn is_blocked(&self, listing: &PFListing) -> bool {
+        let character_name = listing.character_name.to_lowercase();
+        let world = listing.world.to_lowercase();
+        // keywords are written as plain text, so they're matched before markdown escaping
+        self.authors.iter().any(|(x, y)| *x == character_name && *y == world)
+            || (!self.keywords.is_empty() && !self.keywords.is_match(&scraper_util::unsanitize(&listing.description)))
+    }
+}
+
+// Plain keywords are matched literally
+pub fn get_keyword_pattern(keyword: &str, is_regex: bool) -> String {
+    if is_regex { keyword.to_string() } else { regex::escape(keyword) }
+}
@@ -0,0 +1,328 @@
+use crate::blocklist_util;
+use crate::filter_util::DescriptionFilter;
+use crate::locale_util::Locale;
+use crate::prog_util::PartyType;
+use crate::render_util::{BoardLayout, BoardOverflow};
+use crate::schedule_util::{MAX_REFRESH_MINUTES, MIN_REFRESH_MINUTES};
+use crate::settings_util::{self, GuildSettings};
+use crate::sort_util::SortMode;
+use crate::template_util::{BoardTemplate, TemplateSource};
+use crate::xiv_util;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Bumped when the document changes in a way older bots can't read
+pub const CONFIG_VERSION: i64 = 1;
+pub const FILE_NAME: &str = "trappingway-config.json";
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+pub const MAX_BOARDS: usize = 50;
+
+// A guild's boards, filters and settings, as exported by /config export
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+pub struct GuildConfig {
+    pub version: i64,
+    #[serde(default)]
+    pub settings: SettingsConfig,
+    #[serde(default)]
+    pub blocked_authors: Vec<BlockedAuthor>,
+    #[serde(default)]
+    pub blocked_keywords: Vec<BlockedKeyword>,
+    #[serde(default)]
+    pub boards: Vec<BoardConfig>
+}
+
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsConfig {
+    pub data_center: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub manager_role_id: Option<String>,
+    pub layout: Option<String>,
+    pub allow_statics: Option<bool>,
+    pub include_filter: Option<String>,
+    pub exclude_filter: Option<String>,
+    pub log_channel_id: Option<String>
+}
+
+impl SettingsConfig {
+    pub fn new(settings: &GuildSettings) -> SettingsConfig {
+        SettingsConfig {
+            data_center: settings.data_center.clone(),
+            locale: settings.locale.clone(),
+            timezone: settings.timezone.clone(),
+            manager_role_id: settings.manager_role_id.clone(),
+            layout: settings.layout.clone(),
+            allow_statics: settings.allow_statics.map(|x| x == 1),
+            include_filter: settings.include_filter.clone(),
+            exclude_filter: settings.exclude_filter.clone(),
+            log_channel_id: settings.log_channel_id.clone()
+        }
+    }
+
+    pub fn get_guild_settings(&self) -> GuildSettings {
+        GuildSettings {
+            data_center: self.data_center.clone(),
+            locale: self.locale.clone(),
+            timezone: self.timezone.clone(),
+            manager_role_id: self.manager_role_id.clone(),
+            layout: self.layout.clone(),
+            allow_statics: self.allow_statics.map(|x| if x {1} else {0}),
+            include_filter: self.include_filter.clone(),
+            exclude_filter: self.exclude_filter.clone(),
+            log_channel_id: self.log_channel_id.clone()
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct BlockedAuthor {
+    pub character_name: String,
+    pub world: String
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct BlockedKeyword {
+    pub keyword: String,
+    #[serde(default)]
+    pub is_regex: bool
+}
+
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+pub struct BoardConfig {
+    pub channel_id: String,
+    #[serde(default)]
+    pub channel_name: String, // for mapping onto a server with other channel ids
+    pub data_centers: Vec<String>,
+    #[serde(default)]
+    pub duty_names: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default = "get_true")]
+    pub allow_statics: bool,
+    #[serde(default)]
+    pub filter_case_sensitive: bool,
+    #[serde(default)]
+    pub include_filters: Vec<String>,
+    #[serde(default)]
+    pub exclude_filters: Vec<String>,
+    #[serde(default)]
+    pub sort_mode: Option<String>,
+    #[serde(default)]
+    pub party_type: Option<String>,
+    #[serde(default)]
+    pub min_prog_phase: Option<i64>,
+    #[serde(default)]
+    pub layout: Option<String>,
+    #[serde(default)]
+    pub overflow: Option<String>,
+    #[serde(default)]
+    pub template: Option<TemplateSource>,
+    #[serde(default)]
+    pub recreate_if_deleted: bool,
+    #[serde(default)]
+    pub refresh_minutes: Option<i64>
+}
+
+fn get_true() -> bool {
+    true
+}
+
+impl BoardConfig {
+    // Same as Board::get_title, for the diff
+    pub fn get_title(&self) -> String {
+        let subjects = self.duty_names.iter().map(|x| x.as_str())
+            .chain(self.categories.iter().map(|x| xiv_util::get_category_display_name(x)))
+            .collect::<Vec<&str>>();
+        let data_centers = match xiv_util::get_region_name(&self.data_centers) {
+            Some(region) => region.to_string(),
+            None => self.data_centers.join(", ")
+        };
+        format!("{} - {}", subjects.join(", "), data_centers)
+    }
+
+    // Boards showing the same thing in the same channel are the same board as far as importing goes
+    pub fn is_same_board(&self, other: &BoardConfig) -> bool {
+        self.channel_id == other.channel_id && self.duty_names == other.duty_names && self.categories == other.categories && self.data_centers == other.data_centers
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.data_centers.is_empty() {
+            return Err("No data center given.".to_string());
+        }
+        if let Some(x) = self.data_centers.iter().find(|x| !xiv_util::DATA_CENTERS.contains(&x.as_str())) {
+            return Err(format!("Unknown data center {}.", x));
+        }
+        if self.duty_names.is_empty() && self.categories.is_empty() {
+            return Err("Give at least one duty or category.".to_string());
+        }
+        if let Some(x) = self.categories.iter().find(|x| !xiv_util::PF_CATEGORIES.iter().any(|(y, _)| y == x)) {
+            return Err(format!("Unknown category {}.", x));
+        }
+        // each is saved once per board, a second copy would fail to save
+        for (name, list) in [("duty", &self.duty_names), ("data center", &self.data_centers), ("category", &self.categories)] {
+            if let Some(x) = list.iter().duplicates().next() {
+                return Err(format!("The {} {} is listed twice.", name, x));
+            }
+        }
+        DescriptionFilter::new(&self.include_filters, &self.exclude_filters, self.filter_case_sensitive).map(|_| ())?;
+        check_db_string("sort mode", &self.sort_mode, SortMode::from_db_string(&self.sort_mode).to_db_string())?;
+        check_db_string("layout", &self.layout, BoardLayout::from_db_string(&self.layout).to_db_string())?;
+        check_db_string("overflow", &self.overflow, BoardOverflow::from_db_string(&self.overflow).to_db_string())?;
+        if let Some(x) = &self.party_type {
+            if PartyType::from_db_string(x).is_none() {
+                return Err(format!("Unknown party type {}.", x));
+            }
+        }
+        if let Some(x) = self.min_prog_phase {
+            if !(1..=7).contains(&x) {
+                return Err(format!("Prog phase {} isn't between 1 and 7.", x));
+            }
+        }
+        if let Some(x) = self.refresh_minutes {
+            if !(MIN_REFRESH_MINUTES..=MAX_REFRESH_MINUTES).contains(&x) {
+                return Err(format!("Refresh interval {} isn't between {} and {} minutes.", x, MIN_REFRESH_MINUTES, MAX_REFRESH_MINUTES));
+            }
+        }
+        if let Some(template) = &self.template {
+            BoardTemplate::parse(template).map_err(|e| format!("Invalid template. {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+// The enums fall back to their default for anything they don't know, which would hide typos
+fn check_db_string(name: &str, input: &Option<String>, parsed: Option<&str>) -> Result<(), String> {
+    match input {
+        Some(x) if Some(x.as_str()) != parsed => Err(format!("Unknown {} {}.", name, x)),
+        _ => Ok(())
+    }
+}
+
+fn validate_settings(settings: &SettingsConfig) -> Result<(), String> {
+    if let Some(data_center) = &settings.data_center {
+        if let Some(x) = data_center.split(',').map(|x| x.trim()).find(|x| !xiv_util::DATA_CENTERS.contains(x)) {
+            return Err(format!("Unknown data center {}.", x));
+        }
+    }
+    check_db_string("language", &settings.locale, Locale::from_db_string(&settings.locale).to_db_string())?;
+    check_db_string("layout", &settings.layout, BoardLayout::from_db_string(&settings.layout).to_db_string())?;
+    if let Some(timezone) = &settings.timezone {
+        settings_util::parse_timezone(timezone)?;
+    }
+    for filter in [&settings.include_filter, &settings.exclude_filter] {
+        DescriptionFilter::new(&crate::filter_util::split_patterns(filter), &Vec::new(), false)?;
+    }
+    Ok(())
+}
+
+// Reads and checks a document from /config import
+pub fn parse(text: &str) -> Result<GuildConfig, String> {
+    let value = serde_json::from_str::<serde_json::Value>(text).map_err(|e| format!("The file isn't valid JSON: {}", e))?;
+    match value.get("version").and_then(|x| x.as_i64()) {
+        None => return Err("The file has no version, it doesn't look like a config export.".to_string()),
+        Some(x) if x > CONFIG_VERSION => return Err(format!("The file is version {}, which is newer than this bot understands ({}).", x, CONFIG_VERSION)),
+        Some(_) => {}
+    }
+    let config = serde_json::from_value::<GuildConfig>(value).map_err(|e| format!("The file doesn't match the config format: {}", e))?;
+
+    validate_settings(&config.settings).map_err(|e| format!("Settings: {}", e))?;
+    if config.blocked_authors.len() > blocklist_util::MAX_BLOCKED_AUTHORS {
+        return Err(format!("The file blocks {} characters, the maximum is {}.", config.blocked_authors.len(), blocklist_util::MAX_BLOCKED_AUTHORS));
+    }
+    if config.blocked_keywords.len() > blocklist_util::MAX_BLOCKED_KEYWORDS {
+        return Err(format!("The file blocks {} keywords, the maximum is {}.", config.blocked_keywords.len(), blocklist_util::MAX_BLOCKED_KEYWORDS));
+    }
+    for x in &config.blocked_keywords {
+        crate::filter_util::compile_pattern(&blocklist_util::get_keyword_pattern(&x.keyword, x.is_regex), false).map_err(|e| format!("Blocked keyword: {}", e))?;
+    }
+    if config.boards.len() > MAX_BOARDS {
+        return Err(format!("The file has {} boards, the maximum is {}.", config.boards.len(), MAX_BOARDS));
+    }
+    for (i, board) in config.boards.iter().enumerate() {
+        board.validate().map_err(|e| format!("Board {} ({}): {}", i + 1, board.get_title(), e))?;
+    }
+    Ok(config)
+}
+
+// Takes "old:new" pairs separated by commas, with channel ids or mentions, e.g. "<#123>:<#456>"
+pub fn parse_channel_map(input: &str) -> Result<HashMap<String, String>, String> {
+    let parse_channel = |x: &str| {
+        let id = x.trim().trim_start_matches("<#").trim_end_matches('>');
+        id.parse::<u64>().map(|x| x.to_string()).map_err(|_| format!("`{}` isn't a channel id or mention.", x.trim().replace('`', "'")))
+    };
+    let mut map = HashMap::new();
+    for pair in input.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let (from, to) = pair.split_once(':').ok_or_else(|| format!("`{}` should look like old_channel:new_channel.", pair.replace('`', "'")))?;
+        map.insert(parse_channel(from)?, parse_channel(to)?);
+    }
+    Ok(map)
+}
+
+fn or_none(x: &Option<String>) -> String {
+    x.as_ref().map(|y| format!("`{}`", y.replace('`', "'"))).unwrap_or("None".to_string())
+}
+
+// Lines describing what importing would change. Boards already have their channel mapped onto this server.
+pub fn get_diff(current: &GuildConfig, imported: &GuildConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let (a, b) = (&current.settings, &imported.settings);
+    let bool_str = |x: Option<bool>| x.map(|y| if y { "Yes" } else { "No" }.to_string()).unwrap_or("None".to_string());
+    let settings = [
+        ("Data center", or_none(&a.data_center), or_none(&b.data_center)),
+        ("Language", or_none(&a.locale), or_none(&b.locale)),
+        ("UTC offset", or_none(&a.timezone), or_none(&b.timezone)),
+        ("Manager role", a.manager_role_id.as_ref().map(|x| format!("<@&{}>", x)).unwrap_or("None".to_string()),
+            b.manager_role_id.as_ref().map(|x| format!("<@&{}>", x)).unwrap_or("None".to_string())),
+        ("Layout", or_none(&a.layout), or_none(&b.layout)),
+        ("Allow statics", bool_str(a.allow_statics), bool_str(b.allow_statics)),
+        ("Include filter", or_none(&a.include_filter), or_none(&b.include_filter)),
+        ("Exclude filter", or_none(&a.exclude_filter), or_none(&b.exclude_filter)),
+        ("Log channel", a.log_channel_id.as_ref().map(|x| format!("<#{}>", x)).unwrap_or("None".to_string()),
+            b.log_channel_id.as_ref().map(|x| format!("<#{}>", x)).unwrap_or("None".to_string()))
+    ];
+    for (name, before, after) in settings {
+        if before != after {
+            lines.push(format!("~ **{}:** {} → {}", name, before, after));
+        }
+    }
+
+    let author_str = |x: &BlockedAuthor| format!("{} @ {}", crate::scraper_util::sanitize(x.character_name.to_string()), crate::scraper_util::sanitize(x.world.to_string()));
+    let keyword_str = |x: &BlockedKeyword| format!("`{}`{}", x.keyword.replace('`', "'"), if x.is_regex {" (regex)"} else {""});
+    for x in imported.blocked_authors.iter().filter(|x| !current.blocked_authors.contains(x)) {
+        lines.push(format!("+ **Blocked character:** {}", author_str(x)));
+    }
+    for x in current.blocked_authors.iter().filter(|x| !imported.blocked_authors.contains(x)) {
+        lines.push(format!("- **Blocked character:** {}", author_str(x)));
+    }
+    for x in imported.blocked_keywords.iter().filter(|x| !current.blocked_keywords.contains(x)) {
+        lines.push(format!("+ **Blocked keyword:** {}", keyword_str(x)));
+    }
+    for x in current.blocked_keywords.iter().filter(|x| !imported.blocked_keywords.contains(x)) {
+        lines.push(format!("- **Blocked keyword:** {}", keyword_str(x)));
+    }
+
+    for board in &imported.boards {
+        if current.boards.iter().any(|x| x.is_same_board(board)) {
+            lines.push(format!("= **Board:** {} in <#{}> already exists, skipped", board.get_title(), board.channel_id));
+        } else {
+            lines.push(format!("+ **Board:** {} in <#{}>", board.get_title(), board.channel_id));
+        }
+    }
+    lines
+}
@@ -0,0 +1,88 @@
+use regex::{Regex, RegexBuilder};
+use itertools::Itertools;
+
+pub const MAX_PATTERNS: usize = 10;
+pub const MAX_PATTERN_LENGTH: usize = 200;
+// limits on the compiled program, so a user can't make every refresh crawl
+const REGEX_SIZE_LIMIT: usize = 64 * 1024;
+const REGEX_DFA_SIZE_LIMIT: usize = 256 * 1024;
+const REGEX_NEST_LIMIT: u32 = 16;
+
+// Separator for entering several patterns in one slash command option
+pub const PATTERN_SEPARATOR: char = ';';
+
+#[derive(Debug)]
+#[derive(Default)]
+pub struct DescriptionFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    key: String // the same for filters that match the same descriptions
+}
+
+impl DescriptionFilter {
+    pub fn new(include: &[String], exclude: &[String], case_sensitive: bool) -> Result<DescriptionFilter, String> {
+        if include.len() + exclude.len() > MAX_PATTERNS {
+            return Err(format!("Too many filter patterns ({}), the maximum is {}.", include.len() + exclude.len(), MAX_PATTERNS));
+        }
+        Ok(DescriptionFilter {
+            include: include.iter().map(|x| compile_pattern(x, case_sensitive)).collect::<Result<Vec<_>, _>>()?,
+            exclude: exclude.iter().map(|x| compile_pattern(x, case_sensitive)).collect::<Result<Vec<_>, _>>()?,
+            key: get_key(include, exclude, case_sensitive)
+        })
+    }
+
+    // Used for patterns already in the database. Anything that doesn't compile is logged and skipped
+    // instead of taking the board down.
+    pub fn new_lossy(include: &[String], exclude: &[String], case_sensitive: bool) -> DescriptionFilter {
+        let compile = |patterns: &[String]| patterns.iter().filter_map(|x| {
+            match compile_pattern(x, case_sensitive) {
+                Ok(re) => Some(re),
+                Err(err) => {
+                    println!("Skipping invalid stored description filter {:?}: {}", x, err);
+                    None
+                }
+            }
+        }).collect::<Vec<_>>();
+        DescriptionFilter { include: compile(include), exclude: compile(exclude), key: get_key(include, exclude, case_sensitive) }
+    }
+
+    pub fn get_key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    // Listing passes if any include pattern matches (or there are none) and no exclude pattern matches.
+    pub fn is_match(&self, description: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|re| re.is_match(description)))
+            && !self.exclude.iter().any(|re| re.is_match(description))
+    }
+}
+
+// Pattern order and repeats don't change what a filter matches
+fn get_key(include: &[String], exclude: &[String], case_sensitive: bool) -> String {
+    let normalize = |patterns: &[String]| patterns.iter().map(|x| x.as_str()).sorted().dedup().join("\u{1f}");
+    format!("{}\u{1e}{}\u{1e}{}", normalize(include), normalize(exclude), case_sensitive)
+}
+
+pub fn compile_pattern(pattern: &str, case_sensitive: bool) -> Result<Regex, String> {
+    if pattern.chars().count() > MAX_PATTERN_LENGTH {
+        return Err(format!("Pattern `{}` is longer than {} characters.", pattern, MAX_PATTERN_LENGTH));
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_DFA_SIZE_LIMIT)
+        .nest_limit(REGEX_NEST_LIMIT)
+        .build()
+        .map_err(|err| format!("Pattern `{}` is invalid: {}", pattern, err))
+}
+
+pub fn split_patterns(input: &Option<String>) -> Vec<String> {
+    match input {
+        Some(x) => x.split(PATTERN_SEPARATOR).map(|y| y.trim().to_string()).filter(|y| !y.is_empty()).collect(),
+        None => Vec::new()
+    }
+}
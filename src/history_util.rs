@@ -0,0 +1,105 @@
+use crate::storage_util::{Error, Storage};
+use crate::xiv_util::PFListing;
+use std::collections::HashMap;
+
+// How a listing left xivpf, worked out the first fetch it's missing from
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum Outcome {
+    Filled,
+    Expired,
+    Delisted
+}
+
+impl Outcome {
+    pub fn to_db_string(&self) -> &'static str {
+        match self {
+            Outcome::Filled => "filled",
+            Outcome::Expired => "expired",
+            Outcome::Delisted => "delisted"
+        }
+    }
+
+    // Parties that fill drop off xivpf with at most a slot left, since the last member joins between fetches
+    fn get(filled_count: i64, slot_count: i64, expires_at: i64, ended_at: i64) -> Outcome {
+        if expires_at <= ended_at {
+            Outcome::Expired
+        } else if slot_count - filled_count <= 1 {
+            Outcome::Filled
+        } else {
+            Outcome::Delisted
+        }
+    }
+}
+
+// Ended listings older than this are deleted
+fn get_retention_days() -> i64 {
+    std::env::var("LISTING_HISTORY_RETENTION_DAYS").unwrap_or("90".to_string()).parse::<i64>().unwrap()
+}
+
+// Ended listings older than this only keep their first and last fill counts and descriptions
+fn get_compact_after_days() -> i64 {
+    std::env::var("LISTING_HISTORY_COMPACT_AFTER_DAYS").unwrap_or("7".to_string()).parse::<i64>().unwrap()
+}
+
+// A listing_history row without an outcome yet
+pub struct OpenListing {
+    pub filled_count: i64,
+    pub slot_count: i64,
+    pub description: String,
+    pub last_expires_at: i64
+}
+
+pub struct SeenListing<'a> {
+    pub listing: &'a PFListing,
+    pub listing_id: i64,
+    pub filled_count: i64,
+    pub slot_count: i64,
+    pub is_open: bool, // already recorded, otherwise it's new or back under the same id
+    pub fill_changed: bool,
+    pub description_changed: bool
+}
+
+// What a fetch writes to the history: every listing seen, and how the open listings that are gone ended
+pub struct HistoryChanges<'a> {
+    pub seen: Vec<SeenListing<'a>>,
+    pub ended: Vec<(i64, Outcome)>
+}
+
+pub fn get_changes(mut open_listings: HashMap<i64, OpenListing>, listings: &[PFListing], fetched_at: i64) -> HistoryChanges<'_> {
+    let mut seen = Vec::new();
+    for listing in listings {
+        let listing_id = listing.id as i64;
+        let filled_count = listing.get_filled_count() as i64;
+        let slot_count = listing.slots.len() as i64;
+        let (is_open, fill_changed, description_changed) = match open_listings.remove(&listing_id) {
+            Some(open) => (true, open.filled_count != filled_count || open.slot_count != slot_count, open.description != listing.description),
+            None => (false, true, true)
+        };
+        seen.push(SeenListing { listing, listing_id, filled_count, slot_count, is_open, fill_changed, description_changed });
+    }
+
+    // whatever is still open wasn't in this fetch
+    let ended = open_listings.into_iter()
+        .map(|(listing_id, open)| (listing_id, Outcome::get(open.filled_count, open.slot_count, open.last_expires_at, fetched_at)))
+        .collect();
+    HistoryChanges { seen, ended }
+}
+
+// Records a fetch: new listings, fill and description changes, and how listings that are gone ended
+pub async fn record_listings(storage: &dyn Storage, listings: &[PFListing], fetched_at: i64) -> Result<(), Error> {
+    // an empty fetch is much more likely xivpf having trouble than every party ending at once
+    if listings.is_empty() {
+        return Ok(());
+    }
+    storage.record_listings(listings, fetched_at).await
+}
+
+// Applies the retention and compaction policies. Returns how many listings were deleted.
+pub async fn compact_history(storage: &dyn Storage, now: i64) -> Result<u64, Error> {
+    let delete_before = now - get_retention_days() * 24 * 60 * 60;
+    let compact_before = now - get_compact_after_days() * 24 * 60 * 60;
+
+    storage.compact_history(delete_before, compact_before).await
+}
@@ -0,0 +1,386 @@
+use crate::Board;
+use crate::xiv_util::PFListing;
+use crate::scraper_util;
+use crate::render_util;
+use crate::locale_util::{self, Locale};
+use crate::settings_util;
+use tiny_skia::{Color, Paint, Pixmap, PixmapPaint, FilterQuality, Rect, Transform};
+use fontdue::{Font, FontSettings};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+pub const FILE_NAME: &str = "board.png";
+pub const MAX_LISTINGS: usize = 30;
+
+const WIDTH: u32 = 960;
+const PADDING: f32 = 16.0;
+const ICON_SIZE: f32 = 24.0;
+const ICON_GAP: f32 = 3.0;
+const MAX_SLOTS: usize = 8;
+const TITLE_SIZE: f32 = 22.0;
+const TEXT_SIZE: f32 = 15.0;
+const SMALL_SIZE: f32 = 13.0;
+const LINE_HEIGHT: f32 = 20.0;
+const MAX_DESCRIPTION_LINES: usize = 3;
+
+// discord's dark theme, so the image blends into the embed
+const BACKGROUND: [u8; 3] = [0x2f, 0x31, 0x36];
+const ROW_BACKGROUND: [u8; 3] = [0x36, 0x39, 0x3f];
+const TEXT_COLOR: [u8; 3] = [0xdc, 0xdd, 0xde];
+const MUTED_COLOR: [u8; 3] = [0x96, 0x98, 0x9d];
+const FLAGS_COLOR: [u8; 3] = [0xfa, 0xa6, 0x1a];
+const MARKER_COLOR: [u8; 3] = [0xed, 0x42, 0x45];
+
+lazy_static! {
+    static ref REGULAR_FONT: Font = Font::from_bytes(include_bytes!("../fonts/DejaVuSans.ttf") as &[u8], FontSettings::default()).unwrap();
+    static ref BOLD_FONT: Font = Font::from_bytes(include_bytes!("../fonts/DejaVuSans-Bold.ttf") as &[u8], FontSettings::default()).unwrap();
+    // DejaVu has no CJK glyphs, so those come from this font if the server has one. Passed to render_board
+    // rather than read by it, so tests render the same everywhere.
+    pub static ref FALLBACK_FONT: Option<Font> = load_fallback_font();
+    // job and role icons by file name, e.g. "ninja" or "tankhealer"
+    static ref ICONS: HashMap<String, Pixmap> = load_icons();
+}
+
+fn load_icons() -> HashMap<String, Pixmap> {
+    let mut icons = HashMap::new();
+    let entries = match std::fs::read_dir("emoji") {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Couldn't read the emoji directory: {}", e);
+            return icons;
+        }
+    };
+    for path in entries.filter_map(|x| x.ok()).map(|x| x.path()) {
+        if path.extension().and_then(|x| x.to_str()) != Some("png") {
+            continue;
+        }
+        match Pixmap::load_png(&path) {
+            Ok(icon) => { icons.insert(path.file_stem().unwrap().to_string_lossy().to_string(), icon); }
+            Err(e) => { println!("Couldn't load icon {:?}: {}", path, e); }
+        }
+    }
+    icons
+}
+
+fn load_fallback_font() -> Option<Font> {
+    let path = std::env::var("IMAGE_FALLBACK_FONT").unwrap_or("fonts/fallback.ttf".to_string());
+    let bytes = std::fs::read(&path).ok()?;
+    match Font::from_bytes(bytes, FontSettings::default()) {
+        Ok(font) => Some(font),
+        Err(e) => {
+            println!("Couldn't load the fallback font {}: {}", path, e);
+            None
+        }
+    }
+}
+
+// The font that has a glyph for c, font itself if none does
+fn get_glyph_font<'a>(font: &'a Font, fallback: Option<&'a Font>, c: char) -> &'a Font {
+    match fallback {
+        Some(fallback) if font.lookup_glyph_index(c) == 0 && fallback.lookup_glyph_index(c) != 0 => fallback,
+        _ => font
+    }
+}
+
+fn can_draw(text: &str, fallback: Option<&Font>) -> bool {
+    text.chars().all(|c| c.is_whitespace() || get_glyph_font(&REGULAR_FONT, fallback, c).lookup_glyph_index(c) != 0)
+}
+
+// The board's own strings, or English ones if the fonts can't draw them
+fn get_image_locale(locale: Locale, fallback: Option<&Font>) -> Locale {
+    let strings = locale.get_strings();
+    if [strings.refresh_times, strings.no_listings, strings.listing_times].iter().all(|x| can_draw(x, fallback)) { locale } else { Locale::En }
+}
+
+// The emoji for a slot is named after its icon, e.g. "<:ffxivninja:985322478521966612>" is emoji/ninja.png
+fn get_icon_name(emoji: &str) -> Option<&str> {
+    emoji.split(':').nth(1).map(|x| x.trim_start_matches("ffxiv"))
+}
+
+fn get_rgb(color: u32) -> [u8; 3] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+}
+
+fn get_color(rgb: [u8; 3]) -> Color {
+    Color::from_rgba8(rgb[0], rgb[1], rgb[2], 255)
+}
+
+fn fill_rect(pixmap: &mut Pixmap, x: f32, y: f32, width: f32, height: f32, rgb: [u8; 3]) {
+    if let Some(rect) = Rect::from_xywh(x, y, width, height) {
+        let mut paint = Paint::default();
+        paint.set_color(get_color(rgb));
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+}
+
+fn draw_icon(pixmap: &mut Pixmap, name: &str, x: f32, y: f32) {
+    if let Some(icon) = ICONS.get(name) {
+        let scale = ICON_SIZE / icon.width() as f32;
+        let paint = PixmapPaint { quality: FilterQuality::Bicubic, ..PixmapPaint::default() };
+        pixmap.draw_pixmap(0, 0, icon.as_ref(), &paint, Transform::from_row(scale, 0.0, 0.0, scale, x, y), None);
+    }
+}
+
+fn measure_text(font: &Font, fallback: Option<&Font>, text: &str, size: f32) -> f32 {
+    text.chars().map(|c| get_glyph_font(font, fallback, c).metrics(c, size).advance_width).sum()
+}
+
+// Draws text with its baseline at y, returns the x it ended at
+fn draw_text(pixmap: &mut Pixmap, font: &Font, fallback: Option<&Font>, text: &str, x: f32, y: f32, size: f32, rgb: [u8; 3]) -> f32 {
+    let mut x = x;
+    for c in text.chars() {
+        let (metrics, coverage) = get_glyph_font(font, fallback, c).rasterize(c, size);
+        if let Some(mut glyph) = Pixmap::new(metrics.width as u32, metrics.height as u32) {
+            // pixmaps are premultiplied
+            for (pixel, alpha) in glyph.data_mut().chunks_exact_mut(4).zip(coverage.iter()) {
+                for i in 0..3 {
+                    pixel[i] = (rgb[i] as u32 * *alpha as u32 / 255) as u8;
+                }
+                pixel[3] = *alpha;
+            }
+            let left = (x + metrics.xmin as f32).round() as i32;
+            let top = (y - metrics.height as f32 - metrics.ymin as f32).round() as i32;
+            pixmap.draw_pixmap(left, top, glyph.as_ref(), &PixmapPaint::default(), Transform::identity(), None);
+        }
+        x += metrics.advance_width;
+    }
+    x
+}
+
+// Shortens text to max_width, ending in an ellipsis if anything was cut or if always_ellipsis is set
+fn fit_text(font: &Font, fallback: Option<&Font>, text: &str, size: f32, max_width: f32, always_ellipsis: bool) -> String {
+    if !always_ellipsis && measure_text(font, fallback, text, size) <= max_width {
+        return text.to_string();
+    }
+    let mut result = text.to_string();
+    while !result.is_empty() && measure_text(font, fallback, &format!("{}…", result), size) > max_width {
+        result.pop();
+    }
+    format!("{}…", result.trim_end())
+}
+
+fn wrap_text(font: &Font, fallback: Option<&Font>, text: &str, size: f32, max_width: f32, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+        // a word too long for a line on its own is cut by fit_text
+        if current.is_empty() || measure_text(font, fallback, &candidate, size) <= max_width {
+            current = candidate;
+        } else {
+            lines.push(current);
+            current = word.to_string();
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    let is_cut = lines.len() > max_lines;
+    lines.truncate(max_lines);
+    let last = lines.len().saturating_sub(1);
+    lines.iter().enumerate().map(|(i, line)| fit_text(font, fallback, line, size, max_width, is_cut && i == last)).collect()
+}
+
+// Everything about a listing that gets drawn, worked out up front so the image height is known
+struct Row {
+    icons: Vec<String>,
+    objective_color: Option<[u8; 3]>,
+    author: String,
+    markers: String,
+    times: String,
+    flags: String,
+    description_lines: Vec<String>
+}
+
+impl Row {
+    fn new(board: &Board, listing: &PFListing, refresh_times: &render_util::RefreshTimes, fallback: Option<&Font>) -> Row {
+        let text_x = PADDING * 2.0 + MAX_SLOTS as f32 * (ICON_SIZE + ICON_GAP);
+        let strings = get_image_locale(board.locale, fallback).get_strings();
+        let times = locale_util::fill(strings.listing_times, &[("updated", &listing.last_updated), ("expires", &listing.expires_in)]);
+        let times_width = measure_text(&REGULAR_FONT, fallback, &times, SMALL_SIZE);
+        let markers = render_util::get_markers(listing, refresh_times);
+        let markers = [(markers.is_new, strings.new_marker), (markers.almost_full, strings.almost_full_marker), (markers.expiring_soon, strings.expiring_soon_marker)]
+            .iter().filter(|x| x.0).map(|x| x.1).collect::<Vec<&str>>().join(" · ");
+        let markers_width = if markers.is_empty() { 0.0 } else { measure_text(&BOLD_FONT, fallback, &markers, SMALL_SIZE) + PADDING };
+        let author = scraper_util::unsanitize(&render_util::get_author(board, listing));
+        let text_width = WIDTH as f32 - PADDING * 2.0;
+        Row {
+            icons: listing.slots.iter().filter_map(|x| get_icon_name(&x.get_emoji_string()).map(|y| y.to_string())).collect(),
+            objective_color: listing.get_objective().map(|x| get_rgb(x.get_color())),
+            author: fit_text(&BOLD_FONT, fallback, &author, TEXT_SIZE, WIDTH as f32 - text_x - times_width - markers_width - PADDING * 2.0, false),
+            markers,
+            times,
+            flags: fit_text(&REGULAR_FONT, fallback, &scraper_util::unsanitize(&render_util::get_flags(board, listing, board.duty_names.len() > 1)), TEXT_SIZE, text_width, false),
+            description_lines: wrap_text(&REGULAR_FONT, fallback, &scraper_util::unsanitize(&listing.description), TEXT_SIZE, text_width, MAX_DESCRIPTION_LINES)
+        }
+    }
+
+    fn get_height(&self) -> f32 {
+        let text_lines = self.description_lines.len() + if self.flags.is_empty() { 0 } else { 1 };
+        PADDING / 2.0 + ICON_SIZE + text_lines as f32 * LINE_HEIGHT + PADDING / 2.0
+    }
+
+    fn draw(&self, pixmap: &mut Pixmap, top: f32, fallback: Option<&Font>) {
+        fill_rect(pixmap, PADDING, top, WIDTH as f32 - PADDING * 2.0, self.get_height(), ROW_BACKGROUND);
+        if let Some(rgb) = self.objective_color {
+            fill_rect(pixmap, PADDING, top, 4.0, self.get_height(), rgb);
+        }
+
+        let icons_top = top + PADDING / 2.0;
+        for (i, icon) in self.icons.iter().take(MAX_SLOTS).enumerate() {
+            draw_icon(pixmap, icon, PADDING * 1.5 + i as f32 * (ICON_SIZE + ICON_GAP), icons_top);
+        }
+        let baseline = icons_top + ICON_SIZE - 6.0;
+        let text_x = PADDING * 2.0 + MAX_SLOTS as f32 * (ICON_SIZE + ICON_GAP);
+        let author_end = draw_text(pixmap, &BOLD_FONT, fallback, &self.author, text_x, baseline, TEXT_SIZE, TEXT_COLOR);
+        if !self.markers.is_empty() {
+            draw_text(pixmap, &BOLD_FONT, fallback, &self.markers, author_end + PADDING, baseline, SMALL_SIZE, MARKER_COLOR);
+        }
+        let times_x = WIDTH as f32 - PADDING * 1.5 - measure_text(&REGULAR_FONT, fallback, &self.times, SMALL_SIZE);
+        draw_text(pixmap, &REGULAR_FONT, fallback, &self.times, times_x, baseline, SMALL_SIZE, MUTED_COLOR);
+
+        let mut baseline = icons_top + ICON_SIZE + LINE_HEIGHT - 4.0;
+        if !self.flags.is_empty() {
+            draw_text(pixmap, &REGULAR_FONT, fallback, &self.flags, PADDING * 1.5, baseline, TEXT_SIZE, FLAGS_COLOR);
+            baseline += LINE_HEIGHT;
+        }
+        for line in &self.description_lines {
+            draw_text(pixmap, &REGULAR_FONT, fallback, line, PADDING * 1.5, baseline, TEXT_SIZE, TEXT_COLOR);
+            baseline += LINE_HEIGHT;
+        }
+    }
+}
+
+// Draws the board's title and listings as a PNG. fallback draws the glyphs DejaVu lacks, see FALLBACK_FONT.
+pub fn render_board(board: &Board, listings: &[&PFListing], refresh_times: &render_util::RefreshTimes, fallback: Option<&Font>) -> Result<Vec<u8>, String> {
+    let rows = listings.iter().take(MAX_LISTINGS).map(|x| Row::new(board, x, refresh_times, fallback)).collect::<Vec<Row>>();
+    let header_height = PADDING * 2.0 + TITLE_SIZE;
+    let rows_height = if rows.is_empty() { LINE_HEIGHT + PADDING } else { rows.iter().map(|x| x.get_height() + PADDING / 2.0).sum::<f32>() };
+    let height = (header_height + rows_height + PADDING).ceil() as u32;
+
+    let mut pixmap = Pixmap::new(WIDTH, height).ok_or("Couldn't allocate the board image")?;
+    pixmap.fill(get_color(BACKGROUND));
+    fill_rect(&mut pixmap, 0.0, 0.0, 6.0, height as f32, get_rgb(render_util::get_board_color(board, listings)));
+    let strings = get_image_locale(board.locale, fallback).get_strings();
+    // images can't show discord timestamps, so the refresh times are drawn in the server's timezone
+    let updated = format!("{} ({})", settings_util::format_time(refresh_times.fetched_at, board.utc_offset), settings_util::format_timezone(board.utc_offset));
+    let times = render_util::get_refresh_text(board, get_image_locale(board.locale, fallback), &updated);
+    let times_width = measure_text(&REGULAR_FONT, fallback, &times, SMALL_SIZE);
+    draw_text(&mut pixmap, &REGULAR_FONT, fallback, &times, WIDTH as f32 - PADDING - times_width, PADDING + TITLE_SIZE, SMALL_SIZE, MUTED_COLOR);
+    let title = fit_text(&BOLD_FONT, fallback, &scraper_util::unsanitize(&board.get_title()), TITLE_SIZE, WIDTH as f32 - PADDING * 3.0 - times_width, false);
+    draw_text(&mut pixmap, &BOLD_FONT, fallback, &title, PADDING, PADDING + TITLE_SIZE, TITLE_SIZE, TEXT_COLOR);
+
+    let mut top = header_height;
+    if rows.is_empty() {
+        draw_text(&mut pixmap, &REGULAR_FONT, fallback, strings.no_listings, PADDING, top + LINE_HEIGHT, TEXT_SIZE, MUTED_COLOR);
+    }
+    for row in &rows {
+        row.draw(&mut pixmap, top, fallback);
+        top += row.get_height() + PADDING / 2.0;
+    }
+
+    pixmap.encode_png().map_err(|e| format!("Couldn't encode the board image: {}", e))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{scraper_util, MessageRow, Board};
+    use std::sync::Arc;
+
+    // scrape_example.html was saved at some point, these pin it so the relative times come out the same
+    pub(crate) const FETCHED_AT: i64 = 1_656_000_000;
+    const TIMES: render_util::RefreshTimes = render_util::RefreshTimes { fetched_at: FETCHED_AT };
+
+    pub(crate) fn get_board(duty_name: &str, data_center: &str, locale: Locale) -> Board {
+        Board {
+            message_row: MessageRow { message_id: "1".to_string(), layout: Some("image".to_string()), ..MessageRow::default() },
+            duty_names: vec![duty_name.to_string()],
+            data_centers: vec![data_center.to_string()],
+            categories: Vec::new(),
+            description_filter: Arc::new(Default::default()),
+            blocklist: Arc::new(Default::default()),
+            sort_mode: crate::sort_util::SortMode::from_db_string(&None),
+            party_type: None,
+            layout: render_util::BoardLayout::Image,
+            overflow: render_util::BoardOverflow::Link,
+            locale,
+            utc_offset: 0,
+            template: None
+        }
+    }
+
+    pub(crate) fn get_example_listings(board: &Board) -> Vec<PFListing> {
+        let html = std::fs::read_to_string("scrape_example.html").unwrap();
+        scraper_util::get_listings(html, FETCHED_AT).into_iter()
+            .filter(|x| board.duty_names.contains(&x.title) && board.data_centers.contains(&x.data_center))
+            .take(8)
+            .collect()
+    }
+
+    // Renders the board with DejaVu alone, whatever fallback font the machine has, and compares it with
+    // tests/golden/<name>.png. UPDATE_GOLDEN=1 writes the references instead.
+    fn assert_golden(name: &str, board: &Board, listings: &[PFListing]) {
+        let listings = listings.iter().collect::<Vec<&PFListing>>();
+        let png = render_board(board, &listings, &TIMES, None).unwrap();
+        let path = format!("tests/golden/{}.png", name);
+        if std::env::var("UPDATE_GOLDEN").is_ok() {
+            std::fs::write(&path, &png).unwrap();
+            return;
+        }
+        let expected = Pixmap::load_png(&path).unwrap_or_else(|e| panic!("Couldn't load {}: {}, run with UPDATE_GOLDEN=1 to create it", path, e));
+        let actual = Pixmap::decode_png(&png).unwrap();
+        if (expected.width(), expected.height()) != (actual.width(), actual.height()) || expected.data() != actual.data() {
+            let actual_path = format!("{}/{}.png", std::env::temp_dir().display(), name);
+            std::fs::write(&actual_path, &png).unwrap();
+            panic!("{} doesn't match its reference, the new render is at {}", name, actual_path);
+        }
+    }
+
+    #[test]
+    fn renders_ultimate_board() {
+        let board = get_board("Dragonsong's Reprise (Ultimate)", "Primal", Locale::En);
+        let listings = get_example_listings(&board);
+        assert!(!listings.is_empty());
+        assert_golden("dsr_primal", &board, &listings);
+    }
+
+    #[test]
+    fn renders_savage_board_in_german() {
+        let board = get_board("Asphodelos: The Fourth Circle (Savage)", "Aether", Locale::De);
+        let listings = get_example_listings(&board);
+        assert!(!listings.is_empty());
+        assert_golden("p4s_aether_de", &board, &listings);
+    }
+
+    #[test]
+    fn renders_empty_board() {
+        let board = get_board("The Unending Coil of Bahamut (Ultimate)", "Light", Locale::En);
+        assert_golden("empty", &board, &[]);
+    }
+
+    #[test]
+    fn japanese_falls_back_to_english_without_a_cjk_font() {
+        assert_eq!(get_image_locale(Locale::Ja, None), Locale::En);
+        assert_eq!(get_image_locale(Locale::De, None), Locale::De);
+    }
+
+    #[test]
+    fn fallback_font_draws_what_dejavu_lacks() {
+        // the regular face has a few private use glyphs the bold one doesn't, so it stands in for a CJK font here
+        let c = '\u{f000}';
+        assert_eq!(BOLD_FONT.lookup_glyph_index(c), 0);
+        assert!(std::ptr::eq(get_glyph_font(&BOLD_FONT, Some(&REGULAR_FONT), c), &*REGULAR_FONT));
+        assert!(std::ptr::eq(get_glyph_font(&BOLD_FONT, None, c), &*BOLD_FONT));
+
+        let board = get_board("Dragonsong's Reprise (Ultimate)", "Primal", Locale::En);
+        let mut listings = get_example_listings(&board);
+        listings.truncate(1);
+        listings[0].author = format!("{}{}", c, listings[0].author);
+        let listings = listings.iter().collect::<Vec<&PFListing>>();
+        let without = render_board(&board, &listings, &TIMES, None).unwrap();
+        let with = render_board(&board, &listings, &TIMES, Some(&REGULAR_FONT)).unwrap();
+        assert_ne!(without, with);
+    }
+}
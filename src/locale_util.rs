@@ -0,0 +1,391 @@
+use poise::serenity_prelude as serenity;
+use serenity::json::{JsonMap, Value};
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+#[derive(poise::ChoiceParameter)]
+pub enum Locale {
+    #[name = "English"]
+    En,
+    #[name = "日本語"]
+    Ja,
+    #[name = "Deutsch"]
+    De,
+    #[name = "Français"]
+    Fr
+}
+
+impl Locale {
+    pub fn to_db_string(&self) -> Option<&'static str> {
+        match self {
+            Locale::En => None,
+            Locale::Ja => Some("ja"),
+            Locale::De => Some("de"),
+            Locale::Fr => Some("fr")
+        }
+    }
+
+    pub fn from_db_string(input: &Option<String>) -> Locale {
+        match input.as_deref() {
+            Some("ja") => Locale::Ja,
+            Some("de") => Locale::De,
+            Some("fr") => Locale::Fr,
+            _ => Locale::En
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::Ja => "日本語",
+            Locale::De => "Deutsch",
+            Locale::Fr => "Français"
+        }
+    }
+
+    // Discord's locale code, used for command localizations
+    fn get_discord_code(&self) -> &'static str {
+        match self {
+            Locale::En => "en-US",
+            Locale::Ja => "ja",
+            Locale::De => "de",
+            Locale::Fr => "fr"
+        }
+    }
+
+    pub fn get_strings(&self) -> &'static BoardStrings {
+        match self {
+            Locale::En => &EN_STRINGS,
+            Locale::Ja => &JA_STRINGS,
+            Locale::De => &DE_STRINGS,
+            Locale::Fr => &FR_STRINGS
+        }
+    }
+
+    fn get_command_texts(&self) -> &'static [(&'static str, Option<&'static str>, &'static str)] {
+        match self {
+            Locale::En => &[],
+            Locale::Ja => JA_COMMANDS,
+            Locale::De => DE_COMMANDS,
+            Locale::Fr => FR_COMMANDS
+        }
+    }
+}
+
+// Text that shows up on boards. {placeholders} are filled in with fill().
+pub struct BoardStrings {
+    pub no_listings: &'static str,
+    pub no_listings_footer: &'static str,
+    pub not_shown: &'static str, // {count}
+    pub not_shown_duty: &'static str, // {count}, {duty}
+    pub refresh_times: &'static str, // {updated}, {minutes}
+    pub listing_times: &'static str, // {updated}, {expires}
+    pub table_author: &'static str,
+    pub table_party: &'static str,
+    pub table_updated: &'static str,
+    pub table_description: &'static str,
+    pub previous_page: &'static str,
+    pub next_page: &'static str,
+    // image boards can't draw emoji, so their markers are words
+    pub new_marker: &'static str,
+    pub almost_full_marker: &'static str,
+    pub expiring_soon_marker: &'static str
+}
+
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(template.to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+}
+
+const EN_STRINGS: BoardStrings = BoardStrings {
+    no_listings: "No listings at this time.",
+    no_listings_footer: "Or, there are listings but people on this data center don't have the Remote Party Finder dalamud plugin.",
+    not_shown: "Listings not shown: {count}",
+    not_shown_duty: "{duty} listings not shown: {count}",
+    refresh_times: "Updated {updated} · refreshes every {minutes} min",
+    listing_times: "Updated {updated} · Expires {expires}",
+    table_author: "Author",
+    table_party: "Party",
+    table_updated: "Upd",
+    table_description: "Description",
+    previous_page: "Previous",
+    next_page: "Next",
+    new_marker: "New",
+    almost_full_marker: "Almost full",
+    expiring_soon_marker: "Expiring soon"
+};
+
+const JA_STRINGS: BoardStrings = BoardStrings {
+    no_listings: "現在募集はありません。",
+    no_listings_footer: "または、このデータセンターのプレイヤーがDalamudプラグインのRemote Party Finderを使っていない可能性があります。",
+    not_shown: "非表示の募集: {count}件",
+    not_shown_duty: "{duty}の非表示の募集: {count}件",
+    refresh_times: "最終更新 {updated} · {minutes}分ごとに更新",
+    listing_times: "更新 {updated} · 期限 {expires}",
+    table_author: "募集者",
+    table_party: "人数",
+    table_updated: "更新",
+    table_description: "説明",
+    previous_page: "前へ",
+    next_page: "次へ",
+    new_marker: "新着",
+    almost_full_marker: "残りわずか",
+    expiring_soon_marker: "まもなく期限"
+};
+
+const DE_STRINGS: BoardStrings = BoardStrings {
+    no_listings: "Derzeit keine Einträge.",
+    no_listings_footer: "Oder es gibt Einträge, aber auf diesem Rechenzentrum nutzt niemand das Dalamud-Plugin Remote Party Finder.",
+    not_shown: "Nicht angezeigte Einträge: {count}",
+    not_shown_duty: "Nicht angezeigte {duty}-Einträge: {count}",
+    refresh_times: "Aktualisiert {updated} · alle {minutes} Min.",
+    listing_times: "Aktualisiert {updated} · Läuft ab {expires}",
+    table_author: "Autor",
+    table_party: "Gr.",
+    table_updated: "Akt",
+    table_description: "Beschreibung",
+    previous_page: "Zurück",
+    next_page: "Weiter",
+    new_marker: "Neu",
+    almost_full_marker: "Fast voll",
+    expiring_soon_marker: "Läuft bald ab"
+};
+
+const FR_STRINGS: BoardStrings = BoardStrings {
+    no_listings: "Aucune annonce pour le moment.",
+    no_listings_footer: "Ou bien il y a des annonces, mais personne sur ce centre de données n'utilise le plugin Dalamud Remote Party Finder.",
+    not_shown: "Annonces non affichées : {count}",
+    not_shown_duty: "Annonces {duty} non affichées : {count}",
+    refresh_times: "Mis à jour {updated} · toutes les {minutes} min",
+    listing_times: "Mise à jour {updated} · Expire {expires}",
+    table_author: "Auteur",
+    table_party: "Grp",
+    table_updated: "MàJ",
+    table_description: "Description",
+    previous_page: "Précédent",
+    next_page: "Suivant",
+    new_marker: "Nouveau",
+    almost_full_marker: "Presque complet",
+    expiring_soon_marker: "Expire bientôt"
+};
+
+// (path, name, description). The path is the command, subcommand and option names joined by dots.
+// Option names stay in English so they line up with what people paste from guides.
+const JA_COMMANDS: &[(&str, Option<&str>, &str)] = &[
+    ("display_xivpfs", Some("募集ボード"), "FFXIVのパーティ募集をメッセージに表示します。既定では5分ごとに更新されます。"),
+    ("display_xivpfs.channel", None, "チャンネル"),
+    ("display_xivpfs.data_center", None, "データセンターまたは地域（カンマ区切り）"),
+    ("display_xivpfs.allow_statics", None, "固定募集も表示する"),
+    ("display_xivpfs.duty_name", None, "コンテンツ（カンマ区切り）"),
+    ("display_xivpfs.category", None, "募集カテゴリ（カンマ区切り、例: The Hunt, Deep Dungeons）"),
+    ("display_xivpfs.include_filter", None, "表示する説明文の正規表現（;区切り、いずれかに一致すれば表示）"),
+    ("display_xivpfs.exclude_filter", None, "除外する説明文の正規表現（;区切り、いずれかに一致すれば非表示）"),
+    ("display_xivpfs.filter_case_sensitive", None, "フィルターで大文字と小文字を区別する（既定: false）"),
+    ("display_xivpfs.sort", None, "並び順（既定: xivpfの順）"),
+    ("display_xivpfs.party_type", None, "練習・クリア・消化・周回・初見のパーティのみ表示"),
+    ("display_xivpfs.min_prog_phase", None, "このフェーズ以降を練習中のパーティのみ表示（絶）"),
+    ("display_xivpfs.layout", None, "ボードのレイアウト（既定: フィールド）"),
+    ("display_xivpfs.overflow", None, "収まらない募集の扱い（既定: xivpfへのリンク）"),
+    ("display_xivpfs.recreate_if_deleted", None, "メッセージが削除されたら投稿し直す（既定: false）"),
+    ("display_xivpfs.refresh_minutes", None, "更新の間隔（分）。静かなコンテンツは長めでも十分です（既定: 5）"),
+    ("blocklist", Some("ブロックリスト"), "このサーバーの全ボードで、特定のキャラクターやキーワードの募集を非表示にします。"),
+    ("blocklist.add_author", Some("作成者追加"), "キャラクターの募集を非表示にします。"),
+    ("blocklist.add_author.character_name", None, "キャラクター名（例: Chad Mayro）"),
+    ("blocklist.add_author.world", None, "ホームワールド（例: Cactuar）"),
+    ("blocklist.remove_author", Some("作成者削除"), "キャラクターの募集を再び表示します。"),
+    ("blocklist.remove_author.character_name", None, "キャラクター名"),
+    ("blocklist.remove_author.world", None, "ホームワールド"),
+    ("blocklist.add_keyword", Some("キーワード追加"), "説明文にキーワードを含む、または正規表現に一致する募集を非表示にします。"),
+    ("blocklist.add_keyword.keyword", None, "キーワード（大文字と小文字を区別しない）"),
+    ("blocklist.add_keyword.is_regex", None, "キーワードを正規表現として扱う（既定: false）"),
+    ("blocklist.remove_keyword", Some("キーワード削除"), "キーワードによる非表示を解除します。"),
+    ("blocklist.remove_keyword.keyword", None, "追加したときのキーワードまたは正規表現"),
+    ("blocklist.remove_keyword.is_regex", None, "正規表現として追加したか（両方で追加した場合に必要）"),
+    ("blocklist.list", Some("一覧"), "このサーバーでブロックしているキャラクターとキーワードを表示します。"),
+    ("language", Some("言語"), "このサーバーのボードの言語を設定します。"),
+    ("language.language", None, "言語"),
+    ("template", Some("テンプレート"), "テンプレートでボードの表示を変更します。"),
+    ("template.edit", Some("編集"), "ボードのテンプレートを、現在のものかプリセットからプレビュー付きで編集します。"),
+    ("template.edit.board", None, "ボードのメッセージリンクまたはID"),
+    ("template.edit.preset", None, "ボードのテンプレートの代わりにプリセットから始める"),
+    ("template.reset", Some("リセット"), "ボードをレイアウトでの表示に戻します。"),
+    ("template.reset.board", None, "ボードのメッセージリンクまたはID"),
+    ("settings", Some("設定"), "新しいボードに使うこのサーバーの既定値を表示・変更します。"),
+    ("settings.view", Some("表示"), "このサーバーの設定を表示します。"),
+    ("settings.set", Some("変更"), "指定した設定を変更します。新しいボードで省略した項目に使われます。"),
+    ("settings.set.data_center", None, "既定のデータセンターまたは地域（カンマ区切り）"),
+    ("settings.set.language", None, "このサーバーのボードの言語"),
+    ("settings.set.timezone", None, "画像ボードの時刻の固定UTCオフセット（例: UTC+9）。夏時間には対応しません"),
+    ("settings.set.manager_role", None, "メンバーをキックする権限のほかにボードを管理できるロール"),
+    ("settings.set.layout", None, "既定のボードのレイアウト"),
+    ("settings.set.allow_statics", None, "既定で固定募集も表示する"),
+    ("settings.set.include_filter", None, "既定で表示する説明文の正規表現（;区切り）"),
+    ("settings.set.exclude_filter", None, "既定で除外する説明文の正規表現（;区切り）"),
+    ("settings.set.log_channel", None, "ボードの更新が止まったときや再開したときに知らせるチャンネル"),
+    ("settings.reset", Some("リセット"), "設定を既定に戻します。"),
+    ("settings.reset.setting", None, "リセットする設定"),
+    ("config", Some("構成"), "このサーバーのボード、フィルター、設定を別のチャンネル構成やボットに移します。"),
+    ("config.export", Some("エクスポート"), "このサーバーのボード、フィルター、設定をファイルに保存します。"),
+    ("config.import", Some("インポート"), "/config exportのファイルからボードを追加し、フィルターと設定を取り込みます。プレビュー付き。"),
+    ("config.import.file", None, "/config exportのファイル"),
+    ("config.import.channel_map", None, "代わりに投稿するチャンネル（例: #旧:#新、既定は同じID、次に同じ名前）")
+];
+
+const DE_COMMANDS: &[(&str, Option<&str>, &str)] = &[
+    ("display_xivpfs", Some("pf_anzeigen"), "Zeigt FFXIV-Gruppensuche-Einträge in einer Nachricht an. Wird standardmäßig alle 5 Minuten aktualisiert."),
+    ("display_xivpfs.channel", None, "Kanal"),
+    ("display_xivpfs.data_center", None, "Rechenzentren oder Regionen, durch Kommas getrennt"),
+    ("display_xivpfs.allow_statics", None, "Statics erlauben"),
+    ("display_xivpfs.duty_name", None, "Inhalte, durch Kommas getrennt"),
+    ("display_xivpfs.category", None, "PF-Kategorien, durch Kommas getrennt (z. B. The Hunt, Deep Dungeons)"),
+    ("display_xivpfs.include_filter", None, "Einschluss-Regexe, durch ; getrennt (Eintrag wird gezeigt, wenn eine passt)"),
+    ("display_xivpfs.exclude_filter", None, "Ausschluss-Regexe, durch ; getrennt (Eintrag wird ausgeblendet, wenn eine passt)"),
+    ("display_xivpfs.filter_case_sensitive", None, "Groß-/Kleinschreibung in Filtern beachten (Standard: false)"),
+    ("display_xivpfs.sort", None, "Sortierung (Standard: Reihenfolge von xivpf)"),
+    ("display_xivpfs.party_type", None, "Nur Prog-, Clear-, Reclear-, Farm- oder Lerngruppen anzeigen"),
+    ("display_xivpfs.min_prog_phase", None, "Nur Gruppen ab dieser Phase anzeigen (Ultimates)"),
+    ("display_xivpfs.layout", None, "Layout der Tafel (Standard: Felder)"),
+    ("display_xivpfs.overflow", None, "Umgang mit Einträgen, die nicht passen (Standard: Link zu xivpf)"),
+    ("display_xivpfs.recreate_if_deleted", None, "Nachricht neu senden, wenn sie gelöscht wird (Standard false)"),
+    ("display_xivpfs.refresh_minutes", None, "Minuten zwischen Aktualisierungen, ruhige Inhalte brauchen weniger (Standard 5)"),
+    ("blocklist", Some("sperrliste"), "Blendet Einträge bestimmter Charaktere oder mit bestimmten Stichwörtern auf allen Tafeln aus."),
+    ("blocklist.add_author", Some("autor_hinzufügen"), "Blendet Einträge eines Charakters aus."),
+    ("blocklist.add_author.character_name", None, "Charaktername, z. B. Chad Mayro"),
+    ("blocklist.add_author.world", None, "Heimatwelt, z. B. Cactuar"),
+    ("blocklist.remove_author", Some("autor_entfernen"), "Zeigt Einträge eines Charakters wieder an."),
+    ("blocklist.remove_author.character_name", None, "Charaktername"),
+    ("blocklist.remove_author.world", None, "Heimatwelt"),
+    ("blocklist.add_keyword", Some("stichwort_hinzufügen"), "Blendet Einträge aus, deren Beschreibung ein Stichwort enthält oder auf eine Regex passt."),
+    ("blocklist.add_keyword.keyword", None, "Stichwort (ohne Beachtung der Groß-/Kleinschreibung)"),
+    ("blocklist.add_keyword.is_regex", None, "Stichwort als Regex behandeln (Standard: false)"),
+    ("blocklist.remove_keyword", Some("stichwort_entfernen"), "Blendet Einträge mit einem Stichwort nicht mehr aus."),
+    ("blocklist.remove_keyword.keyword", None, "Stichwort oder Regex, wie es hinzugefügt wurde"),
+    ("blocklist.remove_keyword.is_regex", None, "Ob es als Regex hinzugefügt wurde, nötig wenn es auf beide Arten hinzugefügt wurde"),
+    ("blocklist.list", Some("liste"), "Listet die gesperrten Charaktere und Stichwörter dieses Servers auf."),
+    ("language", Some("sprache"), "Legt die Sprache der Tafeln dieses Servers fest."),
+    ("language.language", None, "Sprache"),
+    ("template", Some("vorlage"), "Ändert mit einer Vorlage, wie eine Tafel dargestellt wird."),
+    ("template.edit", Some("bearbeiten"), "Bearbeitet die Vorlage einer Tafel mit Vorschau, ausgehend von der aktuellen oder einem Preset."),
+    ("template.edit.board", None, "Link oder ID der Tafel-Nachricht"),
+    ("template.edit.preset", None, "Mit einem Preset statt der Vorlage der Tafel beginnen"),
+    ("template.reset", Some("zurücksetzen"), "Stellt die Tafel wieder mit ihrem Layout dar."),
+    ("template.reset.board", None, "Link oder ID der Tafel-Nachricht"),
+    ("settings", Some("einstellungen"), "Zeigt und ändert die Standardwerte dieses Servers für neue Tafeln."),
+    ("settings.view", Some("anzeigen"), "Zeigt die Einstellungen dieses Servers."),
+    ("settings.set", Some("ändern"), "Ändert die angegebenen Einstellungen. Neue Tafeln nutzen sie für Ausgelassenes."),
+    ("settings.set.data_center", None, "Standard-Rechenzentren oder Regionen, kommagetrennt"),
+    ("settings.set.language", None, "Sprache der Tafeln dieses Servers"),
+    ("settings.set.timezone", None, "Fester UTC-Versatz für Zeiten auf Bild-Tafeln, z. B. UTC+9. Ohne Sommerzeit"),
+    ("settings.set.manager_role", None, "Rolle, die neben Mitgliedern mit Kick-Recht Tafeln verwalten darf"),
+    ("settings.set.layout", None, "Standard-Layout der Tafeln"),
+    ("settings.set.allow_statics", None, "Statics standardmäßig erlauben"),
+    ("settings.set.include_filter", None, "Standard-Regexes zum Einschließen, getrennt durch ;"),
+    ("settings.set.exclude_filter", None, "Standard-Regexes zum Ausschließen, getrennt durch ;"),
+    ("settings.set.log_channel", None, "Kanal für Meldungen, wenn eine Tafel nicht mehr oder wieder aktualisiert wird"),
+    ("settings.reset", Some("zurücksetzen"), "Setzt eine Einstellung auf den Standard zurück."),
+    ("settings.reset.setting", None, "Zurückzusetzende Einstellung"),
+    ("config", Some("konfiguration"), "Überträgt Tafeln, Filter und Einstellungen dieses Servers auf andere Kanäle oder einen anderen Bot."),
+    ("config.export", Some("exportieren"), "Speichert Tafeln, Filter und Einstellungen dieses Servers in einer Datei."),
+    ("config.import", Some("importieren"), "Fügt die Tafeln aus einer /config export-Datei hinzu und übernimmt Filter und Einstellungen."),
+    ("config.import.file", None, "Datei aus /config export"),
+    ("config.import.channel_map", None, "Stattdessen zu nutzende Kanäle, z. B. #alt:#neu (Standard: gleiche ID, dann gleicher Name)")
+];
+
+const FR_COMMANDS: &[(&str, Option<&str>, &str)] = &[
+    ("display_xivpfs", Some("afficher_pf"), "Affiche les annonces de recherche d'équipe FFXIV dans un message. Mis à jour toutes les 5 minutes par défaut."),
+    ("display_xivpfs.channel", None, "Salon"),
+    ("display_xivpfs.data_center", None, "Centres de données ou régions, séparés par des virgules"),
+    ("display_xivpfs.allow_statics", None, "Autoriser les statics"),
+    ("display_xivpfs.duty_name", None, "Missions, séparées par des virgules"),
+    ("display_xivpfs.category", None, "Catégories, séparées par des virgules (ex. The Hunt, Deep Dungeons)"),
+    ("display_xivpfs.include_filter", None, "Regex d'inclusion, séparées par ; (l'annonce s'affiche si l'une correspond)"),
+    ("display_xivpfs.exclude_filter", None, "Regex d'exclusion, séparées par ; (l'annonce est masquée si l'une correspond)"),
+    ("display_xivpfs.filter_case_sensitive", None, "Filtres sensibles à la casse (par défaut : false)"),
+    ("display_xivpfs.sort", None, "Ordre des annonces (par défaut : ordre de xivpf)"),
+    ("display_xivpfs.party_type", None, "N'afficher que les groupes prog, clear, reclear, farm ou apprentissage"),
+    ("display_xivpfs.min_prog_phase", None, "N'afficher que les groupes à partir de cette phase (fatals)"),
+    ("display_xivpfs.layout", None, "Mise en page du tableau (par défaut : champs)"),
+    ("display_xivpfs.overflow", None, "Que faire des annonces en trop (par défaut : lien vers xivpf)"),
+    ("display_xivpfs.recreate_if_deleted", None, "Republier le message s'il est supprimé (par défaut false)"),
+    ("display_xivpfs.refresh_minutes", None, "Minutes entre les mises à jour, plus lent pour les contenus calmes (par défaut 5)"),
+    ("blocklist", Some("liste_noire"), "Masque les annonces de certains personnages ou mots-clés sur tous les tableaux du serveur."),
+    ("blocklist.add_author", Some("ajouter_auteur"), "Masque les annonces d'un personnage."),
+    ("blocklist.add_author.character_name", None, "Nom du personnage, ex. Chad Mayro"),
+    ("blocklist.add_author.world", None, "Monde d'origine, ex. Cactuar"),
+    ("blocklist.remove_author", Some("retirer_auteur"), "Réaffiche les annonces d'un personnage."),
+    ("blocklist.remove_author.character_name", None, "Nom du personnage"),
+    ("blocklist.remove_author.world", None, "Monde d'origine"),
+    ("blocklist.add_keyword", Some("ajouter_mot_clé"), "Masque les annonces dont la description contient un mot-clé ou correspond à une regex."),
+    ("blocklist.add_keyword.keyword", None, "Mot-clé (insensible à la casse)"),
+    ("blocklist.add_keyword.is_regex", None, "Traiter le mot-clé comme une regex (par défaut : false)"),
+    ("blocklist.remove_keyword", Some("retirer_mot_clé"), "Ne masque plus les annonces contenant un mot-clé."),
+    ("blocklist.remove_keyword.keyword", None, "Mot-clé ou regex, tel qu'il a été ajouté"),
+    ("blocklist.remove_keyword.is_regex", None, "S'il a été ajouté comme regex, nécessaire s'il a été ajouté des deux façons"),
+    ("blocklist.list", Some("liste"), "Liste les personnages et mots-clés bloqués sur ce serveur."),
+    ("language", Some("langue"), "Définit la langue des tableaux de ce serveur."),
+    ("language.language", None, "Langue"),
+    ("template", Some("modèle"), "Change l'affichage d'un tableau avec un modèle."),
+    ("template.edit", Some("modifier"), "Modifie le modèle d'un tableau avec aperçu, à partir de l'actuel ou d'un préréglage."),
+    ("template.edit.board", None, "Lien ou ID du message du tableau"),
+    ("template.edit.preset", None, "Partir d'un préréglage au lieu du modèle du tableau"),
+    ("template.reset", Some("réinitialiser"), "Affiche de nouveau le tableau avec sa disposition."),
+    ("template.reset.board", None, "Lien ou ID du message du tableau"),
+    ("settings", Some("paramètres"), "Affiche et modifie les valeurs par défaut du serveur pour les nouveaux tableaux."),
+    ("settings.view", Some("afficher"), "Affiche les paramètres de ce serveur."),
+    ("settings.set", Some("modifier"), "Modifie les paramètres donnés. Les nouveaux tableaux les utilisent pour ce qui est omis."),
+    ("settings.set.data_center", None, "Centres de données ou régions par défaut, séparés par des virgules"),
+    ("settings.set.language", None, "Langue des tableaux de ce serveur"),
+    ("settings.set.timezone", None, "Décalage UTC fixe des heures des tableaux image, ex. UTC+9. Sans heure d'été"),
+    ("settings.set.manager_role", None, "Rôle pouvant gérer les tableaux, en plus des membres pouvant expulser"),
+    ("settings.set.layout", None, "Disposition par défaut des tableaux"),
+    ("settings.set.allow_statics", None, "Autoriser les statics par défaut"),
+    ("settings.set.include_filter", None, "Regex d'inclusion par défaut, séparées par ;"),
+    ("settings.set.exclude_filter", None, "Regex d'exclusion par défaut, séparées par ;"),
+    ("settings.set.log_channel", None, "Salon où signaler qu'un tableau ne se met plus ou de nouveau à jour"),
+    ("settings.reset", Some("réinitialiser"), "Remet un paramètre à sa valeur par défaut."),
+    ("settings.reset.setting", None, "Paramètre à réinitialiser"),
+    ("config", Some("configuration"), "Transfère les tableaux, filtres et paramètres de ce serveur vers d'autres salons ou un autre bot."),
+    ("config.export", Some("exporter"), "Enregistre les tableaux, filtres et paramètres de ce serveur dans un fichier."),
+    ("config.import", Some("importer"), "Ajoute les tableaux d'un fichier /config export et reprend ses filtres et paramètres."),
+    ("config.import.file", None, "Fichier de /config export"),
+    ("config.import.channel_map", None, "Salons à utiliser à la place, ex. #ancien:#nouveau (défaut : même ID, puis même nom)")
+];
+
+// Adds name and description localizations to commands poise built, including their subcommands and options
+pub fn localize_commands(commands: &mut serenity::CreateApplicationCommands) {
+    for command in commands.0.iter_mut() {
+        localize_command(command, "");
+    }
+}
+
+fn localize_command(command: &mut Value, parent_path: &str) {
+    let object = match command.as_object_mut() {
+        Some(x) => x,
+        None => return
+    };
+    let name = object.get("name").and_then(|x| x.as_str()).unwrap_or_default();
+    let path = if parent_path.is_empty() { name.to_string() } else { format!("{}.{}", parent_path, name) };
+
+    let mut names = JsonMap::new();
+    let mut descriptions = JsonMap::new();
+    for locale in [Locale::Ja, Locale::De, Locale::Fr] {
+        if let Some((_, name, description)) = locale.get_command_texts().iter().find(|x| x.0 == path) {
+            if let Some(name) = name {
+                names.insert(locale.get_discord_code().to_string(), Value::from(*name));
+            }
+            descriptions.insert(locale.get_discord_code().to_string(), Value::from(*description));
+        }
+    }
+    if !names.is_empty() {
+        object.insert("name_localizations".to_string(), Value::from(names));
+    }
+    if !descriptions.is_empty() {
+        object.insert("description_localizations".to_string(), Value::from(descriptions));
+    }
+
+    if let Some(Value::Array(options)) = object.get_mut("options") {
+        for option in options {
+            localize_command(option, &path);
+        }
+    }
+}
@@ -1,86 +1,183 @@
 mod xiv_util;
 mod scraper_util;
+mod filter_util;
+mod sort_util;
+mod blocklist_util;
+mod prog_util;
+mod render_util;
+mod locale_util;
+mod image_util;
+mod template_util;
+mod history_util;
+mod settings_util;
+mod config_util;
+mod quarantine_util;
+mod refresh_util;
+mod schedule_util;
+mod storage_util;
+mod sqlite_util;
+#[cfg(feature = "postgres")]
+mod postgres_util;
 
 use stopwatch::{Stopwatch};
-use std::{time::Duration, sync::Mutex, sync::Arc};
+use std::{time::Duration, sync::Mutex, sync::Arc, sync::atomic::AtomicBool, sync::atomic::Ordering};
 use tokio::{task, time};
 use poise::serenity_prelude as serenity;
 use futures::{Stream, StreamExt};
 use poise::command;
+use poise::Modal;
 use crate::serenity::http::Http;
 use regex::Regex;
 use lazy_static::lazy_static;
 use std::cmp;
+use std::collections::{HashMap, HashSet};
+use itertools::Itertools;
+use storage_util::MessageRow;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const HISTORY_COMPACTION_INTERVAL_SECONDS: u64 = 60*60;
 type Context<'a> = poise::Context<'a, Data, Error>;
 
 // User data, which is stored and accessible in all command invocations
 struct Data {
-    database:sqlx::SqlitePool,
-    pf_listings: Mutex<Vec<xiv_util::PFListing>>
+    database: Arc<dyn storage_util::Storage>,
+    pf_listings: Mutex<Arc<Vec<xiv_util::PFListing>>>, // swapped whole on each fetch, see get_listings
+    has_fetched: AtomicBool, // pf_listings came from xivpf rather than the sample file
+    refresh_times: Mutex<render_util::RefreshTimes>,
+    description_filters: Mutex<HashMap<String, Arc<filter_util::DescriptionFilter>>>, // compiled once per message_id
+    guild_blocklists: Mutex<HashMap<String, Arc<blocklist_util::GuildBlocklist>>>, // per guild_id, dropped when a blocklist changes
+    board_templates: Mutex<HashMap<String, Option<Arc<template_util::BoardTemplate>>>>, // per message_id, dropped when a template changes
+    payload_hashes: Mutex<HashMap<String, (String, i64)>> // per message_id, the hash and time of the last edit, also kept in the db across restarts
+}
+
+// Completes the last entry of a comma separated list
+fn autocomplete_list(options: Vec<&'static str>, partial: &str) -> Vec<String> {
+    let (completed, last) = match partial.rfind(',') {
+        Some(i) => (&partial[..i + 1], partial[i + 1..].trim_start()),
+        None => ("", partial)
+    };
+    options.into_iter()
+        .filter(|name| name.to_lowercase().starts_with(&last.to_lowercase()))
+        .map(|name| if completed.is_empty() { name.to_string() } else { format!("{} {}", completed, name) })
+        .take(25)
+        .collect()
 }
 
 async fn autocomplete_datacenter(_ctx: Context<'_>, partial: String) -> impl Stream<Item = String> {
-    futures::stream::iter(&["Crystal", "Aether", "Primal", "Elemental", "Gaia", "Mana", "Chaos", "Light", "Materia"])
-        .filter(move |name| futures::future::ready(name.starts_with(&partial)))
-        .map(|name| name.to_string())
+    futures::stream::iter(autocomplete_list(xiv_util::REGIONS.iter().chain(xiv_util::DATA_CENTERS.iter()).copied().collect(), &partial))
 }
 
 async fn autocomplete_duty(_ctx: Context<'_>, partial: String) -> impl Stream<Item = String> {
-    futures::stream::iter(&["The Weapon's Refrain (Ultimate)", "The Unending Coil of Bahamut (Ultimate)", "The Epic of Alexander (Ultimate)", "Dragonsong's Reprise (Ultimate)",
-        "Abyssos: The Fifth Circle (Savage)", "Abyssos: The Sixth Circle (Savage)", "Abyssos: The Seventh Circle (Savage)", "Abyssos: The Eighth Circle (Savage)"])
-        .filter(move |name| futures::future::ready(name.starts_with(&partial)))
-        .map(|name| name.to_string())
-}
-
-fn get_embed(data_center: String, duty_name: String, listings: Vec<&xiv_util::PFListing>) -> serenity::builder::CreateEmbed {    
-    let mut embed = serenity::builder::CreateEmbed::default();
-    embed.color(xiv_util::get_color_from_duty(&duty_name));
-    embed.title(format!("{} - {}", duty_name, data_center));
-    let max_to_take = std::env::var("MAX_LISTINGS_IN_POST").expect("missing MAX_LISTINGS_IN_POST").parse::<i32>().unwrap();
-    let not_taken = std::cmp::max(0, (listings.len() as i32) - max_to_take);
-
-    for listing in listings.iter().take(max_to_take as usize) {
-        let author = &listing.author;
-        let role_icons_str = listing.slots.iter().map(|x| x.get_emoji_string()).collect::<Vec<String>>().join(" ");
-        embed.field(author, role_icons_str, true);
-        if listing.flags.chars().count() == 0 {
-            embed.field("\u{200b}", &listing.description, true);
-        } else {
-            embed.field(&listing.flags, &listing.description, true);
+    futures::stream::iter(autocomplete_list(vec!["The Weapon's Refrain (Ultimate)", "The Unending Coil of Bahamut (Ultimate)", "The Epic of Alexander (Ultimate)", "Dragonsong's Reprise (Ultimate)",
+        "Abyssos: The Fifth Circle (Savage)", "Abyssos: The Sixth Circle (Savage)", "Abyssos: The Seventh Circle (Savage)", "Abyssos: The Eighth Circle (Savage)"], &partial))
+}
+
+async fn autocomplete_category(_ctx: Context<'_>, partial: String) -> impl Stream<Item = String> {
+    futures::stream::iter(autocomplete_list(xiv_util::PF_CATEGORIES.iter().map(|(_, display_name)| *display_name).collect(), &partial))
+}
+
+fn parse_list(input: &str) -> Vec<String> {
+    input.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).unique().collect()
+}
+
+// Expands regions into their data centers
+fn parse_data_centers(input: &str) -> Result<Vec<String>, String> {
+    let mut data_centers: Vec<String> = Vec::new();
+    for entry in parse_list(input) {
+        let expanded = match xiv_util::get_region_data_centers(&entry) {
+            Some(x) => x,
+            None => match xiv_util::DATA_CENTERS.iter().find(|x| x.eq_ignore_ascii_case(&entry)) {
+                Some(x) => vec![*x],
+                None => return Err(format!("Unknown data center or region {}.", entry))
+            }
+        };
+        for data_center in expanded {
+            if !data_centers.iter().any(|x| x == data_center) {
+                data_centers.push(data_center.to_string());
+            }
         }
+    }
+    if data_centers.is_empty() {
+        return Err("No data center given.".to_string());
+    }
+    Ok(data_centers)
+}
+
+// Aliases can name the same category twice, e.g. "HighEndDuty, high-end duty"
+fn parse_categories(input: &str) -> Result<Vec<String>, String> {
+    let categories = parse_list(input).iter().map(|x| {
+        match xiv_util::parse_category(x) {
+            Some(category) => Ok(category.to_string()),
+            None => Err(format!("Unknown category {}.", x))
+        }
+    }).collect::<Result<Vec<String>, String>>()?;
+    Ok(categories.into_iter().unique().collect())
+}
+
+struct Board {
+    message_row: MessageRow,
+    duty_names: Vec<String>,
+    data_centers: Vec<String>,
+    categories: Vec<String>,
+    description_filter: Arc<filter_util::DescriptionFilter>,
+    blocklist: Arc<blocklist_util::GuildBlocklist>,
+    sort_mode: sort_util::SortMode,
+    party_type: Option<prog_util::PartyType>,
+    layout: render_util::BoardLayout,
+    overflow: render_util::BoardOverflow,
+    locale: locale_util::Locale,
+    utc_offset: i32, // minutes, for times drawn into images
+    template: Option<Arc<template_util::BoardTemplate>>
+}
 
-        // embed.field("\u{200b}", "\u{200b}", true);
-        embed.field(format!("<:ffxivstopwatch:987141580869730324> {}", listing.last_updated), format!("<:ffxivhourglass:987141579879878676> {}", listing.expires_in), true);
+impl Board {
+    // Duty names followed by category display names
+    fn get_subjects(&self) -> Vec<&str> {
+        self.duty_names.iter().map(|x| x.as_str())
+            .chain(self.categories.iter().map(|x| xiv_util::get_category_display_name(x)))
+            .collect()
     }
 
-    if listings.len() == 0 {
-        embed.description("No listings at this time.");
-        let mut footer = serenity::builder::CreateEmbedFooter::default();
-        footer.text("Or, there are listings but people on this data center don't have the Remote Party Finder dalamud plugin.");
-        embed.set_footer(footer);
+    fn get_title(&self) -> String {
+        let data_centers = match xiv_util::get_region_name(&self.data_centers) {
+            Some(region) => region.to_string(),
+            None => self.data_centers.join(", ")
+        };
+        format!("{} - {}", self.get_subjects().join(", "), data_centers)
     }
 
-    if not_taken > 0 {
-        embed.field("\u{200b}", format!("[{} {} listing{} not shown.](https://xivpf.com/listings)", not_taken, duty_name, if not_taken == 1 {""} else {"s"}), false);
+    // Boards with the same filter key show the same listings
+    fn get_filter_key(&self) -> String {
+        let sorted = |x: &Vec<String>| x.iter().sorted().cloned().collect::<Vec<String>>();
+        // an empty blocklist is the same in every guild
+        let blocklist = if self.blocklist.is_empty() { "" } else { self.message_row.guild_id.as_str() };
+        format!("{:?}|{:?}|{:?}|{}|{:?}|{:?}|{:?}|{:?}|{:?}", sorted(&self.data_centers), sorted(&self.duty_names), sorted(&self.categories),
+            self.message_row.allow_statics.unwrap_or(1), self.description_filter.get_key(), blocklist, self.party_type, self.message_row.min_prog_phase, self.sort_mode)
     }
 
-    embed
-}
+    // Boards with the same render key show the same embeds and image. Order matters here, it's how titles and sections are laid out.
+    fn get_render_key(&self) -> String {
+        // templates belong to one board
+        let template = if self.template.is_some() { self.message_row.message_id.as_str() } else { "" };
+        format!("{}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{:?}", self.get_filter_key(), self.duty_names, self.categories, self.data_centers,
+            self.layout, self.overflow, self.locale, self.utc_offset, template)
+    }
 
-#[derive(Debug)]
-#[allow(dead_code)]
-#[derive(Default)]
-struct MessageRow {
-    message_id: String,
-    channel_id: String,
-    data_center: String,
-    guild_id: String,
-    duty_name: String,
-    is_news: Option<i64>,
-    allow_statics: Option<i64>,
-    description_regex_filter: Option<String>
+    fn get_color(&self) -> u32 {
+        if let Some(color) = self.template.as_ref().and_then(|x| x.color) {
+            return color;
+        }
+        if self.categories.is_empty() {
+            return xiv_util::get_color_from_duties(&self.duty_names);
+        }
+        let colors = self.categories.iter().map(|x| xiv_util::get_color_from_category(x)).collect::<Vec<u32>>();
+        if self.duty_names.is_empty() && colors.iter().all(|x| *x == colors[0]) {
+            colors[0]
+        } else {
+            xiv_util::get_color_from_duty("")
+        }
+    }
 }
 
 fn parse_time_remaining(last_updated: &str) -> i32 {
@@ -96,159 +193,545 @@ fn parse_time_remaining(last_updated: &str) -> i32 {
     minutes_since_update
 }
 
-fn filter_listings<'a>(message_row: &MessageRow, pf_listings: &'a Vec<xiv_util::PFListing>, description_regex_filter: &Option<String>) -> Vec<&'a xiv_util::PFListing> {
+fn filter_listings<'a>(board: &Board, pf_listings: &'a Vec<xiv_util::PFListing>) -> Vec<&'a xiv_util::PFListing> {
     lazy_static! {
         // if this is a match, don't show listing
         static ref RE: Regex = Regex::new(&std::env::var("DESCRIPTION_REGEX").unwrap_or(r".{3,32}#[0-9]{4}".to_string())).unwrap();
     }
     let min_minutes_since_update = (std::env::var("MIN_MINUTES_SINCE_UPDATE").unwrap_or("5".to_string())).parse::<i32>().unwrap(); // don't show pf's last updated more than 5 mins ago
     let min_slots = (std::env::var("MIN_SLOTS").unwrap_or("5".to_string())).parse::<usize>().unwrap();
-    let message_allows_statics = if message_row.allow_statics.unwrap_or(1) == 1 { true } else { false };
-
-    let data_center = message_row.data_center.to_string();
-    let duty_name = message_row.duty_name.to_string();
+    let message_allows_statics = if board.message_row.allow_statics.unwrap_or(1) == 1 { true } else { false };
 
     let mut filtered_listings = pf_listings.iter()
         .filter(|x| {
-            let is_static_ad = RE.is_match(&x.description.replace("​", "")) || x.slots.len() < min_slots;
+            let description = x.description.replace("​", "");
+            let is_static_ad = RE.is_match(&description) || x.slots.len() < min_slots;
 
-            // condition 1: data center must match
-            x.data_center == data_center 
+            // condition 1: data center must be one of the board's
+            board.data_centers.contains(&x.data_center)
 
-            // condition 2: title must match
-            && x.title == duty_name
+            // condition 2: title must be one of the board's duties, or category one of the board's categories
+            && (board.duty_names.contains(&x.title) || board.categories.contains(&x.pf_category))
 
-            // condition 3: user description filter regex must not match
-            && match description_regex_filter {
-                Some(filter) => { 
-                    let user_regex = Regex::new(&filter);
-                    match user_regex {
-                        Ok(re) => {
-                            re.is_match(&x.description.replace("​", ""))
-                        }
-                        Err(err) => {
-                            println!("Somehow an invalid user regex slipped through. Err. {}", err);
-                            true
-                        }
-                    }
-                }
-                None => true
-            }
+            // condition 3: user description filters must match
+            && board.description_filter.is_match(&description)
 
             // condition 4: message allows statics or it's not a static ad
             && (message_allows_statics || !is_static_ad)
+
+            // condition 5: author and description aren't on the guild's blocklist
+            && !board.blocklist.is_blocked(x)
+
+            // condition 6: party type and prog point match, if the board asks for them
+            && board.party_type.map(|y| x.prog.party_types.contains(&y)).unwrap_or(true)
+            && board.message_row.min_prog_phase.map(|y| x.prog.get_phase() as i64 >= y).unwrap_or(true)
     });
     
 
     let filtermax = filtered_listings.clone().map(|x| parse_time_remaining(&x.last_updated)).min().unwrap_or(5);
     let max = cmp::min(cmp::max(filtermax, min_minutes_since_update), 15);
-    filtered_listings.filter(|x| {
+    let mut result = filtered_listings.filter(|x| {
             let minutes_since_update = parse_time_remaining(&x.last_updated);
             minutes_since_update <= max
-        }).collect()
-}
-
-async fn update_message(message_row_ref: &MessageRow, data: &Data, http: std::sync::Arc<Http>) -> Result<u32, Error> {
-    let mut sw0 = Stopwatch::start_new();
-
-    let message_row = message_row_ref.to_owned();
-    let message_id_str = message_row.message_id.to_string();
-    let message_id = message_id_str.parse::<u64>().expect("Unable to parse channel id");        
-    let channel_id = message_row.channel_id.parse::<u64>().expect("Unable to parse channel id");
-    let data_center = message_row.data_center.to_string();
-    let duty_name = message_row.duty_name.to_string();
-    let is_news = message_row.is_news.to_owned();
-    let filter_regex = &message_row.description_regex_filter;
-    let message_result = http.get_message(channel_id, message_id).await;
-    sw0.stop();
-
-    match message_result {
-        Ok(mut message) => {
-            let mut sw1 = Stopwatch::start_new();
-            let embed = {
-                let pf_listings = data.pf_listings.lock().unwrap();
-                let filtered_listings = filter_listings(message_row, &pf_listings, &filter_regex);
-                get_embed(data_center, duty_name, filtered_listings)
-            };
-            sw1.stop();
-            let mut sw2 = Stopwatch::start_new();
-
-            let result = message.edit(&http, |m| m.set_embed(embed)).await;
-            sw2.stop();
-
-
-            match result {
-                Ok(_a) => { 
-                    //println!("Successfully edited message, sw0 time: {}, sw1 time: {}, sw2 time: {}", sw0.elapsed_ms(), sw1.elapsed_ms(), sw2.elapsed_ms()); 
+        }).collect();
+    sort_util::sort_listings(&mut result, board.sort_mode);
+    result
+}
+
+// The listings as last fetched. Fetching swaps in a new Arc, so nobody holds the lock while filtering.
+fn get_listings(data: &Data) -> Arc<Vec<xiv_util::PFListing>> {
+    Arc::clone(&data.pf_listings.lock().unwrap())
+}
+
+async fn get_description_filter(message_row: &MessageRow, data: &Data) -> Arc<filter_util::DescriptionFilter> {
+    if let Some(filter) = data.description_filters.lock().unwrap().get(&message_row.message_id) {
+        return Arc::clone(filter);
+    }
+
+    let patterns = data.database.get_description_filters(&message_row.message_id)
+        .await
+        .unwrap();
+    let include = patterns.iter().filter(|(_, is_exclude)| !is_exclude).map(|(pattern, _)| pattern.to_string()).collect::<Vec<String>>();
+    let exclude = patterns.iter().filter(|(_, is_exclude)| *is_exclude).map(|(pattern, _)| pattern.to_string()).collect::<Vec<String>>();
+    let case_sensitive = message_row.filter_case_sensitive.unwrap_or(0) == 1;
+    let filter = Arc::new(filter_util::DescriptionFilter::new_lossy(&include, &exclude, case_sensitive));
+
+    data.description_filters.lock().unwrap().insert(message_row.message_id.to_string(), Arc::clone(&filter));
+    filter
+}
+
+async fn get_guild_blocklist(guild_id: &str, data: &Data) -> Arc<blocklist_util::GuildBlocklist> {
+    if let Some(blocklist) = data.guild_blocklists.lock().unwrap().get(guild_id) {
+        return Arc::clone(blocklist);
+    }
+
+    let authors = data.database.get_blocked_authors(guild_id)
+        .await
+        .unwrap();
+    let keywords = data.database.get_blocked_keywords(guild_id)
+        .await
+        .unwrap();
+    let blocklist = Arc::new(blocklist_util::GuildBlocklist::new(&authors, &keywords));
+
+    data.guild_blocklists.lock().unwrap().insert(guild_id.to_string(), Arc::clone(&blocklist));
+    blocklist
+}
+
+async fn get_board_template(message_id: &str, data: &Data) -> Option<Arc<template_util::BoardTemplate>> {
+    if let Some(template) = data.board_templates.lock().unwrap().get(message_id) {
+        return template.clone();
+    }
+
+    let source = data.database.get_template(message_id)
+        .await
+        .unwrap();
+    // templates are checked before they're saved, so this only fails if the template language changed since
+    let template = source.and_then(|x| match template_util::BoardTemplate::parse(&x) {
+        Ok(template) => Some(Arc::new(template)),
+        Err(e) => {
+            println!("Ignoring template for {}: {}", message_id, e);
+            None
+        }
+    });
+
+    data.board_templates.lock().unwrap().insert(message_id.to_string(), template.clone());
+    template
+}
+
+async fn get_guild_settings(guild_id: &str, data: &Data) -> Result<settings_util::GuildSettings, Error> {
+    let settings = data.database.get_guild_settings(guild_id).await?;
+    Ok(settings.unwrap_or_default())
+}
+
+async fn save_guild_settings(guild_id: &str, settings: &settings_util::GuildSettings, data: &Data) -> Result<(), Error> {
+    data.database.save_guild_settings(guild_id, settings).await
+}
+
+async fn get_board(message_row: MessageRow, lists: storage_util::MessageLists, settings: &settings_util::GuildSettings, data: &Data) -> Board {
+    let description_filter = get_description_filter(&message_row, data).await;
+    let blocklist = get_guild_blocklist(&message_row.guild_id, data).await;
+    let template = get_board_template(&message_row.message_id, data).await;
+    Board {
+        duty_names: lists.duty_names,
+        data_centers: lists.data_centers,
+        categories: lists.categories,
+        description_filter,
+        blocklist,
+        sort_mode: sort_util::SortMode::from_db_string(&message_row.sort_mode),
+        party_type: message_row.party_type.as_deref().and_then(prog_util::PartyType::from_db_string),
+        layout: render_util::BoardLayout::from_db_string(&message_row.layout),
+        overflow: render_util::BoardOverflow::from_db_string(&message_row.overflow),
+        locale: settings.get_locale(),
+        utc_offset: settings.get_utc_offset(),
+        template,
+        message_row
+    }
+}
+
+async fn load_boards(data: &Data) -> Vec<Board> {
+    let messages = data.database.get_message_rows()
+        .await
+        .unwrap();
+    let mut lists = data.database.get_all_message_lists()
+        .await
+        .unwrap();
+    let guild_settings = data.database.get_all_guild_settings()
+        .await
+        .unwrap();
+    let default_settings = settings_util::GuildSettings::default();
+
+    let mut boards = Vec::new();
+    for message_row in messages {
+        let message_lists = lists.remove(&message_row.message_id).unwrap_or_default();
+        let settings = guild_settings.get(&message_row.guild_id).unwrap_or(&default_settings);
+        boards.push(get_board(message_row, message_lists, settings, data).await);
+    }
+    boards
+}
+
+async fn load_board(message_id: &str, data: &Data) -> Result<Option<Board>, Error> {
+    let message_row = match data.database.get_message_row(message_id).await? {
+        Some(x) => x,
+        None => return Ok(None)
+    };
+    let lists = data.database.get_message_lists(message_id).await?;
+    let settings = get_guild_settings(&message_row.guild_id, data).await?;
+    Ok(Some(get_board(message_row, lists, &settings, data).await))
+}
+
+fn get_http_status(error: &serenity::SerenityError) -> Option<u16> {
+    if let serenity::SerenityError::Http(http_error) = error {
+        if let serenity::HttpError::UnsuccessfulRequest(req_err) = &**http_error {
+            return Some(req_err.status_code.as_u16());
+        }
+    }
+    None
+}
+
+fn get_discord_error_code(error: &serenity::SerenityError) -> Option<isize> {
+    if let serenity::SerenityError::Http(http_error) = error {
+        if let serenity::HttpError::UnsuccessfulRequest(req_err) = &**http_error {
+            return Some(req_err.error.code);
+        }
+    }
+    None
+}
+
+// Keeps a board's extra messages in step with its pages past the first, sending and deleting messages as needed
+async fn update_overflow_messages(board: &Board, data: &Data, http: &Http, pages: Vec<Vec<serenity::CreateEmbed>>) -> Result<(), Error> {
+    let message_id = &board.message_row.message_id;
+    let channel_id = serenity::ChannelId(board.message_row.channel_id.parse::<u64>()?);
+    let existing = data.database.get_overflow_messages(message_id).await?;
+
+    let page_count = pages.len() as i64;
+    for (position, embeds) in pages.into_iter().enumerate() {
+        let position = position as i64;
+        if let Some((_, overflow_message_id)) = existing.iter().find(|(x, _)| *x == position) {
+            let overflow_message_id = overflow_message_id.parse::<u64>()?;
+            match channel_id.edit_message(http, overflow_message_id, |m| m.set_embeds(embeds.clone())).await {
+                Ok(_) => continue,
+                Err(e) if get_http_status(&e) == Some(404) => {
+                    println!("Overflow message {} of {} is gone, sending it again.", overflow_message_id, message_id);
+                }
+                Err(e) => {
+                    println!("Error editing overflow message {}: {}.", overflow_message_id, e);
+                    continue;
+                }
             }
-                Err(e) => { println!("Error editing message: {}.", e); }
+        }
+        let message = channel_id.send_message(http, |m| m.set_embeds(embeds)).await?;
+        let overflow_message_id = message.id.0.to_string();
+        data.database.save_overflow_message(message_id, position, &overflow_message_id).await?;
+    }
+
+    for (position, overflow_message_id) in existing.iter().filter(|(x, _)| *x >= page_count) {
+        if let Err(e) = channel_id.delete_message(http, overflow_message_id.parse::<u64>()?).await {
+            println!("Error deleting overflow message {}: {}.", overflow_message_id, e);
+        }
+        data.database.delete_overflow_message(message_id, *position).await?;
+    }
+    Ok(())
+}
+
+// What a board shows, before picking its page
+struct RenderedBoard {
+    pages: Vec<Vec<serenity::CreateEmbed>>,
+    image: Option<Vec<u8>>
+}
+
+// What one refresh shares between its boards: the listings and times as of its start, and what was filtered and rendered so far.
+// Boards in many guilds often show the same duty and data center, and only the first one does the work.
+struct RefreshCycle {
+    listings: Arc<Vec<xiv_util::PFListing>>,
+    times: render_util::RefreshTimes,
+    filtered: Mutex<HashMap<String, Arc<Vec<xiv_util::PFListing>>>>, // per filter key
+    rendered: Mutex<HashMap<String, Arc<RenderedBoard>>> // per render key
+}
+
+impl RefreshCycle {
+    fn new(data: &Data) -> RefreshCycle {
+        RefreshCycle {
+            listings: get_listings(data),
+            times: *data.refresh_times.lock().unwrap(),
+            filtered: Mutex::new(HashMap::new()),
+            rendered: Mutex::new(HashMap::new())
+        }
+    }
+
+    fn get_filtered(&self, board: &Board) -> Arc<Vec<xiv_util::PFListing>> {
+        let filter_key = board.get_filter_key();
+        if let Some(filtered) = self.filtered.lock().unwrap().get(&filter_key) {
+            return Arc::clone(filtered);
+        }
+        let filtered = Arc::new(filter_listings(board, &self.listings).into_iter().cloned().collect::<Vec<_>>());
+        self.filtered.lock().unwrap().insert(filter_key, Arc::clone(&filtered));
+        filtered
+    }
+
+    fn get_rendered(&self, board: &Board) -> Arc<RenderedBoard> {
+        let render_key = board.get_render_key();
+        if let Some(rendered) = self.rendered.lock().unwrap().get(&render_key) {
+            return Arc::clone(rendered);
+        }
+        let filtered = self.get_filtered(board);
+        let filtered_listings = filtered.iter().collect::<Vec<_>>();
+        let image = render_util::get_board_image(board, filtered_listings.clone(), &self.times);
+        let pages = render_util::get_pages(board, filtered_listings, &self.times, board.overflow.get_max_pages());
+        let rendered = Arc::new(RenderedBoard { pages, image });
+        self.rendered.lock().unwrap().insert(render_key, Arc::clone(&rendered));
+        rendered
+    }
+}
+
+// Edits a board by id, without fetching its message first
+async fn update_message(board: &Board, cycle: &RefreshCycle, data: &Data, http: &Http) -> Result<refresh_util::BoardRefresh, Error> {
+    let message_row = &board.message_row;
+    let message_id = message_row.message_id.parse::<u64>()?;
+    let channel_id = serenity::ChannelId(message_row.channel_id.parse::<u64>()?);
+
+    let mut render_sw = Stopwatch::start_new();
+    // the hash comes from the filtered listings, so an unchanged board isn't rendered at all
+    let requested_page = if board.overflow == render_util::BoardOverflow::Pages { message_row.page.unwrap_or(0) as usize } else { 0 };
+    let payload_hash = render_util::get_payload_hash(board, &cycle.get_filtered(board).iter().collect::<Vec<_>>(), requested_page, &cycle.times);
+    let now = xiv_util::get_unix_time();
+    let last_edit = data.payload_hashes.lock().unwrap().get(&message_row.message_id).cloned()
+        .or_else(|| message_row.payload_hash.clone().zip(message_row.payload_edited_at));
+    if refresh_util::is_unchanged(&payload_hash, last_edit, now) {
+        render_sw.stop();
+        return Ok(refresh_util::BoardRefresh { render_ms: render_sw.elapsed_ms(), is_ok: true, is_unchanged: true, ..Default::default() });
+    }
+
+    let rendered = cycle.get_rendered(board);
+    let mut pages = rendered.pages.clone();
+    let image = rendered.image.clone();
+    let page = cmp::min(requested_page, pages.len() - 1);
+    render_sw.stop();
+
+    let mut discord_sw = Stopwatch::start_new();
+    let page_count = pages.len();
+    let embeds = pages.remove(page);
+    let result = channel_id.edit_message(http, message_id, |m| {
+        if let Some(image) = image {
+            // an empty attachments list drops the old image, so the new one replaces it instead of piling up next to it
+            m.remove_existing_attachment(serenity::AttachmentId(0));
+            m.attachment(serenity::AttachmentType::Bytes { data: image.into(), filename: image_util::FILE_NAME.to_string() });
+        }
+        if board.overflow == render_util::BoardOverflow::Pages {
+            m.set_components(render_util::get_page_buttons(page, page_count, board.locale));
+        }
+        m.set_embeds(embeds)
+    }).await;
+
+    let is_ok = match result {
+        Ok(_) => {
+            data.payload_hashes.lock().unwrap().insert(message_row.message_id.to_string(), (payload_hash.to_string(), now));
+            data.database.set_payload_hash(&message_row.message_id, &payload_hash, now).await?;
+            clear_board_errors(board, data, http).await?;
+            if board.overflow == render_util::BoardOverflow::Messages {
+                // pages now holds everything after the first message
+                if let Err(e) = update_overflow_messages(board, data, http, pages).await {
+                    println!("Error updating overflow messages of {}: {}.", message_id, e);
+                }
             }
+            true
         }
         Err(e) => {
-            println!("Error getting message ({}): {}. Couldn't find message for data center {} duty {}.", message_id, e, &data_center, &duty_name);
-            if let serenity::SerenityError::Http(http_error) = e {
-                if let serenity::HttpError::UnsuccessfulRequest(req_err) = *http_error {
-                    println!("Status code: {}", req_err.status_code);
-                    if req_err.status_code == 403  || req_err.status_code == 404 { // missing access or not found
-                        println!("Removing from db because it's 404 or 403.");
-                        sqlx::query!("DELETE FROM messages WHERE message_id=?", message_id_str)
-                        .fetch_all(&data.database)
-                        .await.expect("Unable to remove that row from DB");
-                    }
-                }
+            println!("Error editing message {} for data center {} duty {}: {}.", message_id, message_row.data_center, message_row.duty_name, e);
+            if let Some(status) = get_http_status(&e).filter(|x| *x == 403 || *x == 404) { // missing access or not found
+                record_board_error(board, status, get_discord_error_code(&e), data, http).await?;
             }
+            false
         }
-    }
-    Ok(1)
+    };
+    discord_sw.stop();
+    Ok(refresh_util::BoardRefresh { render_ms: render_sw.elapsed_ms(), discord_ms: discord_sw.elapsed_ms(), is_ok, is_unchanged: false })
 }
 
-async fn update_messages_rustfn_aux(data: &Data, http: std::sync::Arc<Http>) -> Result<usize, Error> {
-    let messages = sqlx::query_as!(MessageRow, "SELECT message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, description_regex_filter FROM messages")
-        .fetch_all(&data.database)
-        .await
-        .unwrap();
-    let update_count = messages.len();
+// Boards Discord turns the bot away from are kept, and quarantined once it keeps happening
+async fn record_board_error(board: &Board, status: u16, code: Option<isize>, data: &Data, http: &Http) -> Result<(), Error> {
+    let message_row = &board.message_row;
+    if code == Some(quarantine_util::UNKNOWN_MESSAGE) && message_row.recreate_if_deleted.unwrap_or(0) == 1 {
+        let mut board = match load_board(&message_row.message_id, data).await? {
+            Some(x) => x,
+            None => return Ok(())
+        };
+        match recreate_board(&mut board, data, http).await {
+            Ok(()) => return Ok(()),
+            Err(e) => println!("Couldn't post board {} again: {}.", message_row.message_id, e)
+        }
+    }
 
-    let sw1 = Stopwatch::start_new();
+    let reason = quarantine_util::get_reason(status, code);
+    let now = xiv_util::get_unix_time();
+    let error_count = data.database.record_message_error(&message_row.message_id, &reason, now).await?;
+    if message_row.quarantined_at.is_none() && error_count >= quarantine_util::get_errors_before_quarantine() {
+        data.database.quarantine_message(&message_row.message_id, now).await?;
+        println!("Quarantined board {} after {} errors: {}.", message_row.message_id, error_count, reason);
+        let notice = quarantine_util::get_quarantine_notice(&scraper_util::sanitize(board.get_title()), &message_row.channel_id, &reason, error_count);
+        notify_guild(&message_row.guild_id, &notice, data, http).await;
+    }
+    Ok(())
+}
 
-    for message_row in messages {
-        update_message(&message_row, data, Arc::clone(&http)).await?;
+// A board that refreshed fine starts counting its errors over, and leaves quarantine
+async fn clear_board_errors(board: &Board, data: &Data, http: &Http) -> Result<(), Error> {
+    let message_row = &board.message_row;
+    if message_row.error_count.is_none() && message_row.quarantined_at.is_none() {
+        return Ok(());
     }
+    data.database.clear_message_errors(&message_row.message_id).await?;
+    if message_row.quarantined_at.is_some() {
+        println!("Board {} is out of quarantine.", message_row.message_id);
+        let notice = quarantine_util::get_recovery_notice(&scraper_util::sanitize(board.get_title()), &message_row.channel_id);
+        notify_guild(&message_row.guild_id, &notice, data, http).await;
+    }
+    Ok(())
+}
 
-    // println!("Updated {} messages. sw1: {}", update_count, sw1.elapsed_ms());
+// Refreshes the boards that are due. Channels are refreshed concurrently and each channel's boards in order,
+// so no two requests wait on the same rate limit bucket. serenity's ratelimiter holds requests back when a bucket
+// or the global limit runs out.
+async fn update_messages_rustfn_aux(data: &Data, http: std::sync::Arc<Http>, is_startup: bool) -> Result<refresh_util::RefreshReport, Error> {
+    let mut sw = Stopwatch::start_new();
+    let boards = load_boards(data).await;
+    let mut report = refresh_util::RefreshReport::default();
+    let cycle = RefreshCycle::new(data);
+
+    let now = xiv_util::get_unix_time();
+    let (boards, spread): (Vec<Board>, Vec<Board>) = boards.into_iter().partition(|x| !is_startup || !schedule_util::is_overdue(&x.message_row, now));
+    let due = boards.into_iter().filter(|x| schedule_util::is_due(&x.message_row, now)).collect::<Vec<Board>>();
+    // every board looked at gets its next spot, whether it refreshes now or not
+    let next_refresh_times = spread.iter().chain(due.iter())
+        .map(|x| (x.message_row.message_id.to_string(), schedule_util::get_next_refresh_at(&x.message_row, now)))
+        .collect::<Vec<(String, i64)>>();
+    let (due, skipped): (Vec<Board>, Vec<Board>) = due.into_iter().partition(|x| quarantine_util::is_due(&x.message_row, now));
+    report.skipped_count = skipped.len();
+    let channels = due.into_iter().into_group_map_by(|x| x.message_row.channel_id.to_string());
+    report.channel_count = channels.len();
 
-    Ok(update_count)
+    let refreshes = futures::stream::iter(channels.into_values())
+        .map(|boards| {
+            let http = Arc::clone(&http);
+            let cycle = &cycle;
+            async move {
+                let mut refreshes = Vec::new();
+                for board in boards {
+                    match update_message(&board, cycle, data, &http).await {
+                        Ok(refresh) => refreshes.push(refresh),
+                        Err(e) => {
+                            println!("Couldn't refresh board {}: {}", board.message_row.message_id, e);
+                            refreshes.push(refresh_util::BoardRefresh::default());
+                        }
+                    }
+                }
+                refreshes
+            }
+        })
+        .buffer_unordered(refresh_util::get_refresh_concurrency())
+        .collect::<Vec<_>>()
+        .await;
+    for refresh in refreshes.iter().flatten() {
+        report.add(refresh);
+    }
+    report.render_count = cycle.rendered.lock().unwrap().len();
+    // a recreated board's old id matches nothing, which is fine
+    data.database.set_next_refresh_times(&next_refresh_times).await?;
+    sw.stop();
+    report.elapsed_ms = sw.elapsed_ms();
+    Ok(report)
 }
 
-async fn update_messages_rustfn(framework: Arc<poise::Framework<Data, std::boxed::Box<dyn std::error::Error + std::marker::Send + std::marker::Sync>>>, http: std::sync::Arc<Http>) -> Result<usize, Error> {
-    update_messages_rustfn_aux(framework.user_data().await, http).await
+async fn update_messages_rustfn(framework: Arc<poise::Framework<Data, std::boxed::Box<dyn std::error::Error + std::marker::Send + std::marker::Sync>>>, http: std::sync::Arc<Http>, is_startup: bool) -> Result<refresh_util::RefreshReport, Error> {
+    update_messages_rustfn_aux(framework.user_data().await, http, is_startup).await
 }
 
 #[command(slash_command, owners_only, hide_in_help)]
 async fn update_messages(ctx: Context<'_>) -> Result<(), Error> {
     let initial_message = ctx.say("Updating messages...").await;
-    let mut sw = Stopwatch::start_new();
-    let update_count = update_messages_rustfn_aux(&ctx.data(), Arc::clone(&ctx.discord().http)).await?;
-    sw.stop();
-    initial_message?.edit(ctx, |x| x.content(format!("Updated {} messages. Async elapsed time: {}", update_count, sw.elapsed_ms()))).await.expect("update_messages Couldn't update intial message");
+    let report = update_messages_rustfn_aux(&ctx.data(), Arc::clone(&ctx.discord().http), false).await?;
+    initial_message?.edit(ctx, |x| x.content(report.to_string())).await.expect("update_messages Couldn't update intial message");
     Ok(())
 }
 
 
-/// Displays FFXIV party finder listings in a discord message. Updates every 5 minutes.
-#[poise::command(slash_command, required_permissions = "KICK_MEMBERS")]
+/// Displays FFXIV party finder listings in a discord message. Updates every 5 minutes by default.
+#[poise::command(slash_command, check = "is_manager")]
 async fn display_xivpfs(
     ctx: Context<'_>,
     #[description = "Channel"] channel: serenity::Channel,
-    #[description = "Datacenter"] #[autocomplete = "autocomplete_datacenter"] data_center: String,
-    #[description = "Duty"] #[autocomplete = "autocomplete_duty"] duty_name: String,
-    #[description = "Allow Statics"] allow_statics: bool,
-    #[description = "Description filter regex (if pf description contains match, it will be included in post)"] filter_regex: Option<String>
+    #[description = "Data centers or regions, comma separated (default from /settings)"] #[autocomplete = "autocomplete_datacenter"] data_center: Option<String>,
+    #[description = "Allow Statics (default from /settings, otherwise true)"] allow_statics: Option<bool>,
+    #[description = "Duties, comma separated"] #[autocomplete = "autocomplete_duty"] duty_name: Option<String>,
+    #[description = "PF categories, comma separated (e.g. The Hunt, Deep Dungeons)"] #[autocomplete = "autocomplete_category"] category: Option<String>,
+    #[description = "Include filter regexes, separated by ; (pf is shown if its description matches any of them)"] include_filter: Option<String>,
+    #[description = "Exclude filter regexes, separated by ; (pf is hidden if its description matches any of them)"] exclude_filter: Option<String>,
+    #[description = "Match filters case sensitively (default false)"] filter_case_sensitive: Option<bool>,
+    #[description = "Listing order (default xivpf order)"] sort: Option<sort_util::SortMode>,
+    #[description = "Only show prog, clear, reclear, farm or learning parties"] party_type: Option<prog_util::PartyType>,
+    #[description = "Only show parties progging this phase or later (ultimates)"] #[min = 1] #[max = 7] min_prog_phase: Option<i64>,
+    #[description = "Board layout (default from /settings, otherwise fields)"] layout: Option<render_util::BoardLayout>,
+    #[description = "What to do with listings that don't fit (default link to xivpf)"] overflow: Option<render_util::BoardOverflow>,
+    #[description = "Post the board again if its message is deleted (default false)"] recreate_if_deleted: Option<bool>,
+    #[description = "Minutes between updates, slower suits quiet duties (default 5)"] #[min = 5] #[max = 60] refresh_minutes: Option<i64>
 ) -> Result<(), Error> {
     let initial_message = ctx.say(format!("Adding PF listings display...")).await;
     let author_name = &ctx.author().name.to_string();
     println!("display_xivpfs called, author: {}", author_name);
 
+    // anything left out comes from the server's settings
+    let settings = match ctx.guild_id() {
+        Some(guild_id) => get_guild_settings(&guild_id.0.to_string(), ctx.data()).await?,
+        None => settings_util::GuildSettings::default()
+    };
+    let include_filter = include_filter.or_else(|| settings.include_filter.clone());
+    let exclude_filter = exclude_filter.or_else(|| settings.exclude_filter.clone());
+    let allow_statics = allow_statics.or(settings.allow_statics.map(|x| x == 1)).unwrap_or(true);
+    let data_center = match data_center.or_else(|| settings.data_center.clone()) {
+        Some(x) => x,
+        None => {
+            initial_message?.edit(ctx, |x| x.content("Give a data center, or set a default one with /settings set.")).await?;
+            return Ok(());
+        }
+    };
+
+    let include_patterns = filter_util::split_patterns(&include_filter);
+    let exclude_patterns = filter_util::split_patterns(&exclude_filter);
+    let filter_case_sensitive = filter_case_sensitive.unwrap_or(false);
+    let description_filter = match filter_util::DescriptionFilter::new(&include_patterns, &exclude_patterns, filter_case_sensitive) {
+        Ok(filter) => Arc::new(filter),
+        Err(err) => {
+            initial_message?.edit(ctx, |x| x.content(format!("Invalid description filter. {}", err))).await?;
+            return Ok(());
+        }
+    };
+    let data_centers = match parse_data_centers(&data_center) {
+        Ok(x) => x,
+        Err(err) => {
+            initial_message?.edit(ctx, |x| x.content(err)).await?;
+            return Ok(());
+        }
+    };
+    let duty_names = parse_list(&duty_name.unwrap_or_default());
+    let categories = match parse_categories(&category.unwrap_or_default()) {
+        Ok(x) => x,
+        Err(err) => {
+            initial_message?.edit(ctx, |x| x.content(err)).await?;
+            return Ok(());
+        }
+    };
+    if duty_names.is_empty() && categories.is_empty() {
+        initial_message?.edit(ctx, |x| x.content("Give at least one duty or category.")).await?;
+        return Ok(());
+    }
+    let allow_statics_i = if allow_statics {1} else {0};
+    let blocklist = match ctx.guild_id() {
+        Some(guild_id) => get_guild_blocklist(&guild_id.0.to_string(), ctx.data()).await,
+        None => Arc::new(blocklist_util::GuildBlocklist::default())
+    };
+    let sort_mode = sort.unwrap_or(sort_util::SortMode::Default);
+    let layout = layout.unwrap_or(settings.get_layout());
+    let overflow = overflow.unwrap_or(render_util::BoardOverflow::Link);
+    let mut board = Board {
+        message_row: MessageRow { data_center: data_centers.join(", "), allow_statics: Some(allow_statics_i), sort_mode: sort_mode.to_db_string().map(|x| x.to_string()),
+            party_type: party_type.map(|x| x.to_db_string().to_string()), min_prog_phase, layout: layout.to_db_string().map(|x| x.to_string()),
+            overflow: overflow.to_db_string().map(|x| x.to_string()), filter_case_sensitive: Some(if filter_case_sensitive {1} else {0}),
+            recreate_if_deleted: Some(if recreate_if_deleted.unwrap_or(false) {1} else {0}), refresh_minutes, ..MessageRow::default() },
+        duty_names,
+        data_centers,
+        categories,
+        description_filter,
+        blocklist,
+        sort_mode,
+        party_type,
+        layout,
+        overflow,
+        locale: settings.get_locale(),
+        utc_offset: settings.get_utc_offset(),
+        template: None
+    };
+    let duty_name = board.get_subjects().join(", ");
+    board.message_row.duty_name = duty_name.to_string();
+
     let response = match channel.guild() {
         Some(guild_channel) => {
             println!("guild channel kind: {}", guild_channel.kind.name());
@@ -258,29 +741,12 @@ async fn display_xivpfs(
                 let guild_name = ctx.guild().unwrap().name;
                 let guild_id = ctx.guild_id().unwrap().0.to_string();
                 println!("display_xivpfs player name: {}, duty_name: {}, guild name: {}", guild_name, duty_name, author_name);
-                let allow_statics_i = if allow_statics {1} else {0};
-                sqlx::query!("INSERT OR IGNORE INTO guilds(guild_id, guild_name) VALUES(?, ?)", guild_id, guild_name)
-                    .fetch_all(&ctx.data().database)
+                ctx.data().database.add_guild(&guild_id, &guild_name)
                     .await
                     .unwrap();
                 
 
-                    let embed = {
-                    let pf_listings = ctx.data().pf_listings.lock().unwrap();
-                    let filtered_listings = filter_listings(&MessageRow { data_center: data_center.to_string(), duty_name: duty_name.to_string(), allow_statics: Some(allow_statics_i), ..MessageRow::default() }, &pf_listings, &filter_regex);
-                    get_embed(data_center.to_string(), duty_name.to_string(), filtered_listings)
-                };
-                let channel_id = guild_channel.id;
-                let message = channel_id.send_message(&ctx.discord().http, |m| m.set_embed(embed)).await.expect("something");
-                let message_id = message.id.0.to_string();
-                let channel_id_str = channel_id.0.to_string();
-                let guild_id = ctx.guild_id().unwrap().0.to_string();
-                let is_news = guild_channel.kind.name() == "news";
-                sqlx::query!("INSERT INTO messages(message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, description_regex_filter) VALUES(?, ?, ?, ?, ?, ?, ?, ?)", message_id, channel_id_str, guild_id, data_center, duty_name, allow_statics_i, 
-                    is_news, filter_regex)
-                    .fetch_all(&ctx.data().database)
-                    .await
-                    .unwrap();
+                post_board(&mut board, &guild_channel, &include_patterns, &exclude_patterns, ctx.data(), &ctx.discord().http).await?;
                 format!("Created updating message in channel {}", guild_channel.name())
             }
         }
@@ -293,9 +759,869 @@ async fn display_xivpfs(
     Ok(())
 }
 
+// Sends a board's first page as a new message, returns it along with the pages after it
+async fn send_board(board: &Board, channel_id: serenity::ChannelId, data: &Data, http: &Http) -> Result<(serenity::Message, Vec<Vec<serenity::CreateEmbed>>), Error> {
+    let pf_listings = get_listings(data);
+    let filtered_listings = filter_listings(board, &pf_listings);
+    let refresh_times = *data.refresh_times.lock().unwrap();
+    let image = render_util::get_board_image(board, filtered_listings.clone(), &refresh_times);
+    let mut pages = render_util::get_pages(board, filtered_listings, &refresh_times, board.overflow.get_max_pages());
+    let page_count = pages.len();
+    let embeds = pages.remove(0);
+    let message = channel_id.send_message(http, |m| {
+        if let Some(image) = image {
+            m.add_file(serenity::AttachmentType::Bytes { data: image.into(), filename: image_util::FILE_NAME.to_string() });
+        }
+        if board.overflow == render_util::BoardOverflow::Pages {
+            m.set_components(render_util::get_page_buttons(0, page_count, board.locale));
+        }
+        m.set_embeds(embeds)
+    }).await?;
+    Ok((message, pages))
+}
+
+// Sends a new board to a channel and saves it. The board's message_row needs everything but the ids.
+async fn post_board(board: &mut Board, channel: &serenity::GuildChannel, include_patterns: &[String], exclude_patterns: &[String], data: &Data, http: &Http) -> Result<String, Error> {
+    let (message, pages) = send_board(board, channel.id, data, http).await?;
+    let message_id = message.id.0.to_string();
+    let is_news = channel.kind.name() == "news";
+    board.message_row = MessageRow { message_id: message_id.to_string(), channel_id: channel.id.0.to_string(), guild_id: channel.guild_id.0.to_string(),
+        is_news: Some(if is_news {1} else {0}), ..std::mem::take(&mut board.message_row) };
+    let lists = storage_util::MessageLists { duty_names: board.duty_names.clone(), data_centers: board.data_centers.clone(), categories: board.categories.clone() };
+    data.database.create_message(&board.message_row, &lists, include_patterns, exclude_patterns).await?;
+    data.database.set_next_refresh_times(&[(message_id.to_string(), schedule_util::get_next_refresh_at(&board.message_row, xiv_util::get_unix_time()))]).await?;
+    data.description_filters.lock().unwrap().insert(message_id.to_string(), Arc::clone(&board.description_filter));
+    if board.overflow == render_util::BoardOverflow::Messages {
+        update_overflow_messages(board, data, http, pages).await?;
+    }
+    Ok(message_id)
+}
+
+// Posts a board whose message was deleted again in the same channel, keeping its filters and template
+async fn recreate_board(board: &mut Board, data: &Data, http: &Http) -> Result<(), Error> {
+    let channel_id = serenity::ChannelId(board.message_row.channel_id.parse::<u64>()?);
+    let (message, pages) = send_board(board, channel_id, data, http).await?;
+    // the old extra messages would sit above the new board
+    if let Err(e) = update_overflow_messages(board, data, http, Vec::new()).await {
+        println!("Error deleting overflow messages of {}: {}.", board.message_row.message_id, e);
+    }
+    let message_id = message.id.0.to_string();
+    data.database.replace_message_id(&board.message_row.message_id, &message_id).await?;
+    forget_boards(&[board.message_row.message_id.to_string()], data);
+    println!("Board {} was deleted, posted it again as {}.", board.message_row.message_id, message_id);
+    board.message_row.message_id = message_id;
+    board.message_row.page = None;
+    data.database.set_next_refresh_times(&[(board.message_row.message_id.to_string(), schedule_util::get_next_refresh_at(&board.message_row, xiv_util::get_unix_time()))]).await?;
+    if board.overflow == render_util::BoardOverflow::Messages {
+        update_overflow_messages(board, data, http, pages).await?;
+    }
+    let notice = quarantine_util::get_recreated_notice(&scraper_util::sanitize(board.get_title()), &board.message_row.channel_id);
+    notify_guild(&board.message_row.guild_id, &notice, data, http).await;
+    Ok(())
+}
+
+// Says something in the guild's log channel, if it has one
+async fn notify_guild(guild_id: &str, text: &str, data: &Data, http: &Http) {
+    let log_channel_id = match get_guild_settings(guild_id, data).await.ok().and_then(|x| x.log_channel_id).and_then(|x| x.parse::<u64>().ok()) {
+        Some(x) => serenity::ChannelId(x),
+        None => return
+    };
+    if let Err(e) = log_channel_id.send_message(http, |m| m.content(text).allowed_mentions(|x| x.empty_parse())).await {
+        println!("Couldn't send to the log channel of guild {}: {}.", guild_id, e);
+    }
+}
+
+async fn add_guild(ctx: Context<'_>) -> Result<String, Error> {
+    let guild_name = ctx.guild().unwrap().name;
+    let guild_id = ctx.guild_id().unwrap().0.to_string();
+    ctx.data().database.add_guild(&guild_id, &guild_name).await?;
+    Ok(guild_id)
+}
+
+/// Hides listings from specific characters, or with specific keywords, on all of this server's boards.
+#[poise::command(slash_command, guild_only, check = "is_manager",
+    subcommands("blocklist_add_author", "blocklist_remove_author", "blocklist_add_keyword", "blocklist_remove_keyword", "blocklist_list"))]
+async fn blocklist(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Hides listings made by a character.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "add_author")]
+async fn blocklist_add_author(
+    ctx: Context<'_>,
+    #[description = "Character name, e.g. Chad Mayro"] character_name: String,
+    #[description = "Home world, e.g. Cactuar"] world: String
+) -> Result<(), Error> {
+    let guild_id = add_guild(ctx).await?;
+    let character_name = character_name.trim().to_string();
+    let world = world.trim().to_string();
+    let count = ctx.data().database.get_blocked_authors(&guild_id).await?.len();
+    if count >= blocklist_util::MAX_BLOCKED_AUTHORS {
+        ctx.say(format!("This server already blocks {} characters, which is the maximum.", count)).await?;
+        return Ok(());
+    }
+
+    ctx.data().database.add_blocked_author(&guild_id, &character_name, &world).await?;
+    ctx.data().guild_blocklists.lock().unwrap().remove(&guild_id);
+    ctx.say(format!("Listings by {} @ {} will be hidden.", scraper_util::sanitize(character_name), scraper_util::sanitize(world))).await?;
+    Ok(())
+}
+
+/// Shows listings made by a character again.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "remove_author")]
+async fn blocklist_remove_author(
+    ctx: Context<'_>,
+    #[description = "Character name"] character_name: String,
+    #[description = "Home world"] world: String
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().0.to_string();
+    let character_name = character_name.trim().to_string();
+    let world = world.trim().to_string();
+    let removed = ctx.data().database.remove_blocked_author(&guild_id, &character_name, &world).await?;
+    ctx.data().guild_blocklists.lock().unwrap().remove(&guild_id);
+    if !removed {
+        ctx.say(format!("{} @ {} isn't on the blocklist.", scraper_util::sanitize(character_name), scraper_util::sanitize(world))).await?;
+    } else {
+        ctx.say(format!("Removed {} @ {} from the blocklist.", scraper_util::sanitize(character_name), scraper_util::sanitize(world))).await?;
+    }
+    Ok(())
+}
+
+/// Hides listings whose description contains a keyword or matches a regex.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "add_keyword")]
+async fn blocklist_add_keyword(
+    ctx: Context<'_>,
+    #[description = "Keyword (case insensitive)"] keyword: String,
+    #[description = "Treat the keyword as a regex (default false)"] is_regex: Option<bool>
+) -> Result<(), Error> {
+    let is_regex = is_regex.unwrap_or(false);
+    if let Err(err) = filter_util::compile_pattern(&blocklist_util::get_keyword_pattern(&keyword, is_regex), false) {
+        ctx.say(format!("Invalid keyword. {}", err)).await?;
+        return Ok(());
+    }
+
+    let guild_id = add_guild(ctx).await?;
+    let count = ctx.data().database.get_blocked_keywords(&guild_id).await?.len();
+    if count >= blocklist_util::MAX_BLOCKED_KEYWORDS {
+        ctx.say(format!("This server already blocks {} keywords, which is the maximum.", count)).await?;
+        return Ok(());
+    }
+
+    ctx.data().database.add_blocked_keyword(&guild_id, &keyword, is_regex).await?;
+    ctx.data().guild_blocklists.lock().unwrap().remove(&guild_id);
+    ctx.say(format!("Listings matching `{}` will be hidden.", keyword.replace("`", "'"))).await?;
+    Ok(())
+}
+
+/// Stops hiding listings with a keyword.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "remove_keyword")]
+async fn blocklist_remove_keyword(
+    ctx: Context<'_>,
+    #[description = "Keyword or regex, as it was added"] keyword: String,
+    #[description = "Whether it was added as a regex, needed if it was added both ways"] is_regex: Option<bool>
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().0.to_string();
+    let matches = ctx.data().database.get_blocked_keywords(&guild_id).await?.into_iter()
+        .filter(|(x, y)| *x == keyword && is_regex.map(|z| z == *y).unwrap_or(true))
+        .collect::<Vec<(String, bool)>>();
+    if matches.len() > 1 {
+        ctx.say(format!("`{}` is on the blocklist both as a keyword and as a regex. Say which with is_regex.", keyword.replace("`", "'"))).await?;
+        return Ok(());
+    }
+    let removed = match matches.first() {
+        Some((_, is_regex)) => ctx.data().database.remove_blocked_keyword(&guild_id, &keyword, *is_regex).await?,
+        None => false
+    };
+    ctx.data().guild_blocklists.lock().unwrap().remove(&guild_id);
+    if !removed {
+        ctx.say(format!("`{}` isn't on the blocklist.", keyword.replace("`", "'"))).await?;
+    } else {
+        ctx.say(format!("Removed `{}` from the blocklist.", keyword.replace("`", "'"))).await?;
+    }
+    Ok(())
+}
+
+/// Lists this server's blocked characters and keywords.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "list")]
+async fn blocklist_list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().0.to_string();
+    let authors = ctx.data().database.get_blocked_authors(&guild_id)
+        .await?
+        .into_iter().map(|(character_name, world)| format!("{} @ {}", scraper_util::sanitize(character_name), scraper_util::sanitize(world)))
+        .collect::<Vec<String>>();
+    let keywords = ctx.data().database.get_blocked_keywords(&guild_id)
+        .await?
+        .into_iter().map(|(keyword, is_regex)| format!("`{}`{}", keyword.replace("`", "'"), if is_regex {" (regex)"} else {""}))
+        .collect::<Vec<String>>();
+
+    let authors_str = if authors.is_empty() { "None".to_string() } else { authors.join(", ") };
+    let keywords_str = if keywords.is_empty() { "None".to_string() } else { keywords.join(", ") };
+    let mut response = format!("**Blocked characters:** {}\n**Blocked keywords:** {}", authors_str, keywords_str);
+    if response.chars().count() > 2000 { // discord message limit
+        response = response.chars().take(1997).collect::<String>() + "...";
+    }
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Sets the language of this server's boards.
+#[poise::command(slash_command, guild_only, check = "is_manager")]
+async fn language(
+    ctx: Context<'_>,
+    #[description = "Language"] language: locale_util::Locale
+) -> Result<(), Error> {
+    let guild_id = add_guild(ctx).await?;
+    let mut settings = get_guild_settings(&guild_id, ctx.data()).await?;
+    settings.locale = language.to_db_string().map(|x| x.to_string());
+    save_guild_settings(&guild_id, &settings, ctx.data()).await?;
+    ctx.say(format!("This server's boards will be in {} from the next refresh.", language.get_name())).await?;
+    Ok(())
+}
+
+// Members with Kick Members, or the server's manager role, can set up and change boards
+async fn is_manager(ctx: Context<'_>) -> Result<bool, Error> {
+    // slash command interactions come with the member's permissions in the channel
+    let member = match ctx {
+        poise::Context::Application(x) => x.interaction.member(),
+        poise::Context::Prefix(_) => None
+    };
+    let is_manager = match (member, ctx.guild_id()) {
+        (Some(member), Some(guild_id)) => {
+            let manager_role_id = get_guild_settings(&guild_id.0.to_string(), ctx.data()).await?.manager_role_id;
+            member.permissions.map(|x| x.kick_members()).unwrap_or(false)
+                || manager_role_id.map(|x| member.roles.iter().any(|y| y.0.to_string() == x)).unwrap_or(false)
+        }
+        _ => false
+    };
+    if !is_manager {
+        ctx.send(|m| m.content("You need the Kick Members permission or this server's manager role for that.").ephemeral(true)).await?;
+    }
+    Ok(is_manager)
+}
+
+/// Views and changes this server's defaults for new boards.
+#[poise::command(slash_command, guild_only, check = "is_manager", subcommands("settings_view", "settings_set", "settings_reset"))]
+async fn settings(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows this server's settings.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "view")]
+async fn settings_view(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().0.to_string();
+    let settings = get_guild_settings(&guild_id, ctx.data()).await?;
+    let or_none = |x: &Option<String>| x.as_ref().map(|y| format!("`{}`", y.replace('`', "'"))).unwrap_or("None".to_string());
+    let lines = [
+        format!("**Data center:** {}", or_none(&settings.data_center)),
+        format!("**Language:** {}", settings.get_locale().get_name()),
+        format!("**UTC offset:** {} (fixed, doesn't follow daylight saving time)", settings_util::format_timezone(settings.get_utc_offset())),
+        format!("**Manager role:** {}", settings.manager_role_id.as_ref().map(|x| format!("<@&{}>", x)).unwrap_or("None, only members with Kick Members".to_string())),
+        format!("**Layout:** {:?}", settings.get_layout()),
+        format!("**Allow statics:** {}", if settings.allow_statics.unwrap_or(1) == 1 { "Yes" } else { "No" }),
+        format!("**Include filter:** {}", or_none(&settings.include_filter)),
+        format!("**Exclude filter:** {}", or_none(&settings.exclude_filter)),
+        format!("**Log channel:** {}", settings.log_channel_id.as_ref().map(|x| format!("<#{}>", x)).unwrap_or("None".to_string()))
+    ];
+    // the role mention shouldn't ping anyone
+    ctx.send(|m| m.content(lines.join("\n")).allowed_mentions(|x| x.empty_parse())).await?;
+    Ok(())
+}
+
+/// Changes the settings given. New boards use them for anything left out of /display_xivpfs.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "set")]
+async fn settings_set(
+    ctx: Context<'_>,
+    #[description = "Default data centers or regions, comma separated"] #[autocomplete = "autocomplete_datacenter"] data_center: Option<String>,
+    #[description = "Language of this server's boards"] language: Option<locale_util::Locale>,
+    #[description = "Fixed UTC offset for image board times, e.g. UTC+9. Doesn't follow daylight saving"] timezone: Option<String>,
+    #[description = "Role that can manage boards, besides members with Kick Members"] manager_role: Option<serenity::Role>,
+    #[description = "Default board layout"] layout: Option<render_util::BoardLayout>,
+    #[description = "Allow statics by default"] allow_statics: Option<bool>,
+    #[description = "Default include filter regexes, separated by ;"] include_filter: Option<String>,
+    #[description = "Default exclude filter regexes, separated by ;"] exclude_filter: Option<String>,
+    #[description = "Channel to say when a board stops or starts updating again"] log_channel: Option<serenity::Channel>
+) -> Result<(), Error> {
+    let guild_id = add_guild(ctx).await?;
+    let mut settings = get_guild_settings(&guild_id, ctx.data()).await?;
+    let mut changed = Vec::new();
+
+    if let Some(data_center) = data_center {
+        match parse_data_centers(&data_center) {
+            Ok(x) => settings.data_center = Some(x.join(", ")),
+            Err(err) => {
+                ctx.say(err).await?;
+                return Ok(());
+            }
+        }
+        changed.push("data center");
+    }
+    if let Some(language) = language {
+        settings.locale = language.to_db_string().map(|x| x.to_string());
+        changed.push("language");
+    }
+    if let Some(timezone) = timezone {
+        match settings_util::parse_timezone(&timezone) {
+            Ok(x) => settings.timezone = Some(settings_util::format_timezone(x)),
+            Err(err) => {
+                ctx.say(err).await?;
+                return Ok(());
+            }
+        }
+        changed.push("UTC offset");
+    }
+    if let Some(manager_role) = manager_role {
+        settings.manager_role_id = Some(manager_role.id.0.to_string());
+        changed.push("manager role");
+    }
+    if let Some(layout) = layout {
+        settings.layout = layout.to_db_string().map(|x| x.to_string());
+        changed.push("layout");
+    }
+    if let Some(allow_statics) = allow_statics {
+        settings.allow_statics = Some(if allow_statics {1} else {0});
+        changed.push("allow statics");
+    }
+    for (filter, setting, name) in [(include_filter, &mut settings.include_filter, "include filter"), (exclude_filter, &mut settings.exclude_filter, "exclude filter")] {
+        if let Some(filter) = filter {
+            if let Err(err) = filter_util::DescriptionFilter::new(&filter_util::split_patterns(&Some(filter.to_string())), &Vec::new(), false) {
+                ctx.say(format!("Invalid {}. {}", name, err)).await?;
+                return Ok(());
+            }
+            *setting = Some(filter);
+            changed.push(name);
+        }
+    }
+    if let Some(log_channel) = log_channel {
+        match log_channel.guild() {
+            Some(x) if x.kind.name() == "text" || x.kind.name() == "news" => settings.log_channel_id = Some(x.id.0.to_string()),
+            _ => {
+                ctx.say("The log channel has to be a text channel.").await?;
+                return Ok(());
+            }
+        }
+        changed.push("log channel");
+    }
+
+    if changed.is_empty() {
+        ctx.say("Give at least one setting to change.").await?;
+        return Ok(());
+    }
+    save_guild_settings(&guild_id, &settings, ctx.data()).await?;
+    ctx.say(format!("Changed {}. Language and UTC offset apply to existing boards from the next refresh, the rest to new boards.", changed.join(", "))).await?;
+    Ok(())
+}
+
+/// Sets a setting back to its default.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "reset")]
+async fn settings_reset(
+    ctx: Context<'_>,
+    #[description = "Setting to reset"] setting: settings_util::Setting
+) -> Result<(), Error> {
+    let guild_id = add_guild(ctx).await?;
+    let mut settings = get_guild_settings(&guild_id, ctx.data()).await?;
+    settings.reset(setting);
+    save_guild_settings(&guild_id, &settings, ctx.data()).await?;
+    ctx.say(if setting == settings_util::Setting::All { "Reset all settings." } else { "Reset the setting." }).await?;
+    Ok(())
+}
+
+// Takes a message link or id, returns the id if it's one of this server's boards
+async fn get_guild_board_id(ctx: Context<'_>, input: &str) -> Result<Option<String>, Error> {
+    let message_id = match input.trim().trim_end_matches('/').rsplit('/').next().and_then(|x| x.parse::<u64>().ok()) {
+        Some(x) => x.to_string(),
+        None => return Ok(None)
+    };
+    let guild_id = ctx.guild_id().unwrap().0.to_string();
+    let is_guild_message = ctx.data().database.is_guild_message(&message_id, &guild_id).await?;
+    Ok(if is_guild_message { Some(message_id) } else { None })
+}
+
+#[derive(Debug)]
+#[derive(poise::Modal)]
+#[name = "Board template"]
+struct TemplateModal {
+    #[name = "Title"]
+    #[placeholder = "{{title}}"]
+    #[max_length = 200]
+    title: Option<String>,
+    #[name = "Body"]
+    #[placeholder = "{{#listings}}**{{author}}** {{description}}\n{{/listings}}"]
+    #[paragraph]
+    #[max_length = 2000]
+    body: String,
+    #[name = "Footer"]
+    #[max_length = 500]
+    footer: Option<String>,
+    #[name = "Color"]
+    #[placeholder = "#5865F2, empty for the duty's color"]
+    #[max_length = 7]
+    color: Option<String>
+}
+
+/// Changes how a board is drawn with a template.
+#[poise::command(slash_command, guild_only, check = "is_manager", subcommands("template_edit", "template_reset"))]
+async fn template(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Edits a board's template, starting from its current one or a preset, with a preview before saving.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "edit")]
+async fn template_edit(
+    ctx: Context<'_>,
+    #[description = "Board message link or id"] board: String,
+    #[description = "Start from a preset instead of the board's template"] preset: Option<template_util::TemplatePreset>
+) -> Result<(), Error> {
+    let app_ctx = match ctx {
+        poise::Context::Application(x) => x,
+        poise::Context::Prefix(_) => return Ok(())
+    };
+    let message_id = match get_guild_board_id(ctx, &board).await? {
+        Some(x) => x,
+        None => {
+            ctx.send(|m| m.content("That isn't one of this server's boards.").ephemeral(true)).await?;
+            return Ok(());
+        }
+    };
+
+    let current = ctx.data().database.get_template(&message_id).await?;
+    let start = match (preset, current) {
+        (Some(preset), _) => preset.get_source(),
+        (None, Some(current)) => current,
+        (None, None) => template_util::TemplatePreset::Classic.get_source()
+    };
+    let none_if_empty = |x: String| if x.is_empty() { None } else { Some(x) };
+    // the modal has to be the first response
+    let input = TemplateModal::execute_with_defaults(app_ctx, TemplateModal {
+        title: none_if_empty(start.title),
+        body: start.body,
+        footer: none_if_empty(start.footer),
+        color: none_if_empty(start.color)
+    }).await?;
+    let source = template_util::TemplateSource {
+        title: input.title.unwrap_or_default(),
+        body: input.body,
+        footer: input.footer.unwrap_or_default(),
+        color: input.color.unwrap_or_default()
+    };
+    let template = match template_util::BoardTemplate::parse(&source) {
+        Ok(x) => x,
+        Err(e) => {
+            ctx.send(|m| m.content(format!("Invalid template. {}\nNothing was saved.", e)).ephemeral(true)).await?;
+            return Ok(());
+        }
+    };
+
+    let mut preview_board = match load_board(&message_id, ctx.data()).await? {
+        Some(x) => x,
+        None => return Ok(())
+    };
+    preview_board.template = Some(Arc::new(template));
+    let preview = {
+        let pf_listings = get_listings(ctx.data());
+        let filtered_listings = filter_listings(&preview_board, &pf_listings);
+        render_util::get_pages(&preview_board, filtered_listings, &ctx.data().refresh_times.lock().unwrap(), 1).remove(0)
+    };
+    let reply = ctx.send(|m| {
+        m.content("Preview with the current listings:").ephemeral(true).components(|c| c.create_action_row(|row| row
+            .create_button(|b| b.custom_id("xivpf_template_save").label("Save").style(serenity::ButtonStyle::Primary))
+            .create_button(|b| b.custom_id("xivpf_template_cancel").label("Cancel").style(serenity::ButtonStyle::Secondary))));
+        m.embeds = preview;
+        m
+    }).await?;
+
+    let pressed = reply.message().await?
+        .await_component_interaction(ctx.discord())
+        .author_id(ctx.author().id)
+        .timeout(Duration::from_secs(10 * 60))
+        .await;
+    let pressed = match pressed {
+        Some(x) => x,
+        None => {
+            ctx.send(|m| m.content("The preview timed out, nothing was saved.").ephemeral(true)).await?;
+            return Ok(());
+        }
+    };
+    let response = if pressed.data.custom_id == "xivpf_template_save" {
+        ctx.data().database.save_template(&message_id, &source).await?;
+        ctx.data().board_templates.lock().unwrap().remove(&message_id);
+        "Saved. The board will use the template from the next refresh."
+    } else {
+        "Nothing was saved."
+    };
+    pressed.create_interaction_response(&ctx.discord().http, |r| r
+        .kind(serenity::InteractionResponseType::UpdateMessage)
+        .interaction_response_data(|d| d.content(response).components(|c| c))).await?;
+    Ok(())
+}
+
+/// Goes back to drawing a board with its layout.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "reset")]
+async fn template_reset(
+    ctx: Context<'_>,
+    #[description = "Board message link or id"] board: String
+) -> Result<(), Error> {
+    let message_id = match get_guild_board_id(ctx, &board).await? {
+        Some(x) => x,
+        None => {
+            ctx.say("That isn't one of this server's boards.").await?;
+            return Ok(());
+        }
+    };
+    let removed = ctx.data().database.delete_template(&message_id).await?;
+    ctx.data().board_templates.lock().unwrap().remove(&message_id);
+    if !removed {
+        ctx.say("That board doesn't have a template.").await?;
+    } else {
+        ctx.say("Removed the template. The board will use its layout from the next refresh.").await?;
+    }
+    Ok(())
+}
+
+// The guild's boards, filters and settings as a config document
+async fn get_guild_config(guild_id: &str, channel_names: &HashMap<String, String>, data: &Data) -> Result<config_util::GuildConfig, Error> {
+    let settings = get_guild_settings(guild_id, data).await?;
+    let blocked_authors = data.database.get_blocked_authors(guild_id).await?.into_iter()
+        .map(|(character_name, world)| config_util::BlockedAuthor { character_name, world }).collect();
+    let blocked_keywords = data.database.get_blocked_keywords(guild_id).await?.into_iter()
+        .map(|(keyword, is_regex)| config_util::BlockedKeyword { keyword, is_regex }).collect();
+
+    let mut boards = Vec::new();
+    for message_row in data.database.get_guild_message_rows(guild_id).await? {
+        let message_lists = data.database.get_message_lists(&message_row.message_id).await?;
+        let patterns = data.database.get_description_filters(&message_row.message_id).await?;
+        boards.push(config_util::BoardConfig {
+            channel_name: channel_names.get(&message_row.channel_id).cloned().unwrap_or_default(),
+            data_centers: message_lists.data_centers,
+            duty_names: message_lists.duty_names,
+            categories: message_lists.categories,
+            allow_statics: message_row.allow_statics.unwrap_or(1) == 1,
+            filter_case_sensitive: message_row.filter_case_sensitive.unwrap_or(0) == 1,
+            include_filters: patterns.iter().filter(|(_, is_exclude)| !is_exclude).map(|(pattern, _)| pattern.to_string()).collect(),
+            exclude_filters: patterns.iter().filter(|(_, is_exclude)| *is_exclude).map(|(pattern, _)| pattern.to_string()).collect(),
+            template: data.database.get_template(&message_row.message_id).await?,
+            channel_id: message_row.channel_id,
+            sort_mode: message_row.sort_mode,
+            party_type: message_row.party_type,
+            min_prog_phase: message_row.min_prog_phase,
+            layout: message_row.layout,
+            overflow: message_row.overflow,
+            recreate_if_deleted: message_row.recreate_if_deleted.unwrap_or(0) == 1,
+            refresh_minutes: message_row.refresh_minutes
+        });
+    }
+    Ok(config_util::GuildConfig { version: config_util::CONFIG_VERSION, settings: config_util::SettingsConfig::new(&settings), blocked_authors, blocked_keywords, boards })
+}
+
+// A board from an imported config, ready for post_board
+fn get_imported_board(config: &config_util::BoardConfig, settings: &settings_util::GuildSettings, blocklist: Arc<blocklist_util::GuildBlocklist>) -> Board {
+    let sort_mode = sort_util::SortMode::from_db_string(&config.sort_mode);
+    let layout = render_util::BoardLayout::from_db_string(&config.layout);
+    let overflow = render_util::BoardOverflow::from_db_string(&config.overflow);
+    // the config was validated, so nothing here should fail
+    let template = config.template.as_ref().and_then(|x| template_util::BoardTemplate::parse(x).ok()).map(Arc::new);
+    let mut board = Board {
+        message_row: MessageRow { data_center: config.data_centers.join(", "), allow_statics: Some(if config.allow_statics {1} else {0}),
+            filter_case_sensitive: Some(if config.filter_case_sensitive {1} else {0}), sort_mode: config.sort_mode.clone(), party_type: config.party_type.clone(),
+            min_prog_phase: config.min_prog_phase, layout: config.layout.clone(), overflow: config.overflow.clone(),
+            recreate_if_deleted: Some(if config.recreate_if_deleted {1} else {0}), refresh_minutes: config.refresh_minutes, ..MessageRow::default() },
+        duty_names: config.duty_names.clone(),
+        data_centers: config.data_centers.clone(),
+        categories: config.categories.clone(),
+        description_filter: Arc::new(filter_util::DescriptionFilter::new_lossy(&config.include_filters, &config.exclude_filters, config.filter_case_sensitive)),
+        blocklist,
+        sort_mode,
+        party_type: config.party_type.as_deref().and_then(prog_util::PartyType::from_db_string),
+        layout,
+        overflow,
+        locale: settings.get_locale(),
+        utc_offset: settings.get_utc_offset(),
+        template
+    };
+    board.message_row.duty_name = board.get_subjects().join(", ");
+    board
+}
+
+/// Moves this server's boards, filters and settings to another channel layout or bot.
+#[poise::command(slash_command, guild_only, check = "is_manager", subcommands("config_export", "config_import"))]
+async fn config(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Saves this server's boards, filters and settings to a file.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "export")]
+async fn config_export(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let channel_names = guild_id.channels(ctx.discord()).await?.into_iter().map(|(id, x)| (id.0.to_string(), x.name)).collect::<HashMap<_, _>>();
+    let config = get_guild_config(&guild_id.0.to_string(), &channel_names, ctx.data()).await?;
+    let json = serde_json::to_string_pretty(&config)?;
+    ctx.send(|m| m
+        .content(format!("{} boards. Use the file with /config import, here or on another server.", config.boards.len()))
+        .attachment(serenity::AttachmentType::Bytes { data: json.into_bytes().into(), filename: config_util::FILE_NAME.to_string() })
+        .ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Adds the boards from a /config export file and takes its filters and settings, with a preview.
+#[poise::command(slash_command, guild_only, check = "is_manager", rename = "import")]
+async fn config_import(
+    ctx: Context<'_>,
+    #[description = "File from /config export"] file: serenity::Attachment,
+    #[description = "Channels to post in instead, e.g. #old:#new, #old2:#new2 (default same id, then same name)"] channel_map: Option<String>
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let guild_id = ctx.guild_id().unwrap();
+    if file.size > config_util::MAX_FILE_SIZE {
+        ctx.say("That file is too big to be a config export.").await?;
+        return Ok(());
+    }
+    let parsed = String::from_utf8(file.download().await?).map_err(|e| e.to_string()).and_then(|x| config_util::parse(&x));
+    let channel_map = config_util::parse_channel_map(&channel_map.unwrap_or_default());
+    let (mut imported, channel_map) = match (parsed, channel_map) {
+        (Ok(x), Ok(y)) => (x, y),
+        (Err(e), _) | (_, Err(e)) => {
+            ctx.say(format!("Can't import that file. {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    // boards go to the mapped channel, the channel with the same id, or the one with the same name
+    let channels = guild_id.channels(ctx.discord()).await?;
+    let is_board_channel = |x: &serenity::GuildChannel| x.kind.name() == "text" || x.kind.name() == "news";
+    for board in imported.boards.iter_mut() {
+        let channel = match channel_map.get(&board.channel_id) {
+            Some(id) => channels.values().find(|x| x.id.0.to_string() == *id),
+            None => channels.values().find(|x| x.id.0.to_string() == board.channel_id)
+                .or_else(|| channels.values().find(|x| x.name == board.channel_name && is_board_channel(x)))
+        };
+        match channel.filter(|x| is_board_channel(x)) {
+            Some(x) => board.channel_id = x.id.0.to_string(),
+            None => {
+                ctx.say(format!("There's no text channel for the board {} from #{} ({}). Map it to one with channel_map.",
+                    board.get_title(), scraper_util::sanitize(board.channel_name.to_string()), board.channel_id)).await?;
+                return Ok(());
+            }
+        }
+    }
+    // role ids only carry over within the same server
+    if let Some(role_id) = &imported.settings.manager_role_id {
+        let roles = guild_id.roles(ctx.discord()).await?;
+        if !roles.keys().any(|x| x.0.to_string() == *role_id) {
+            imported.settings.manager_role_id = None;
+        }
+    }
+    if let Some(channel_id) = &imported.settings.log_channel_id {
+        let channel_id = channel_map.get(channel_id).unwrap_or(channel_id);
+        imported.settings.log_channel_id = channels.values().find(|x| x.id.0.to_string() == *channel_id && is_board_channel(x)).map(|x| x.id.0.to_string());
+    }
+
+    let channel_names = channels.iter().map(|(id, x)| (id.0.to_string(), x.name.to_string())).collect::<HashMap<_, _>>();
+    let current = get_guild_config(&guild_id.0.to_string(), &channel_names, ctx.data()).await?;
+    let diff = config_util::get_diff(&current, &imported);
+    if diff.is_empty() {
+        ctx.say("The file matches this server, there's nothing to import.").await?;
+        return Ok(());
+    }
+    let mut content = format!("Importing will make these changes. Boards that aren't in the file are kept.\n{}", diff.join("\n"));
+    if content.chars().count() > 2000 { // discord message limit
+        content = content.chars().take(1997).collect::<String>() + "...";
+    }
+    let reply = ctx.send(|m| m
+        .content(content)
+        .allowed_mentions(|x| x.empty_parse())
+        .ephemeral(true)
+        .components(|c| c.create_action_row(|row| row
+            .create_button(|b| b.custom_id("xivpf_config_import").label("Import").style(serenity::ButtonStyle::Primary))
+            .create_button(|b| b.custom_id("xivpf_config_cancel").label("Cancel").style(serenity::ButtonStyle::Secondary))))).await?;
+    let pressed = reply.message().await?
+        .await_component_interaction(ctx.discord())
+        .author_id(ctx.author().id)
+        .timeout(Duration::from_secs(10 * 60))
+        .await;
+    let pressed = match pressed {
+        Some(x) if x.data.custom_id == "xivpf_config_import" => x,
+        Some(x) => {
+            x.create_interaction_response(&ctx.discord().http, |r| r
+                .kind(serenity::InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content("Nothing was imported.").components(|c| c))).await?;
+            return Ok(());
+        }
+        None => {
+            ctx.send(|m| m.content("Timed out, nothing was imported.").ephemeral(true)).await?;
+            return Ok(());
+        }
+    };
+    pressed.create_interaction_response(&ctx.discord().http, |r| r
+        .kind(serenity::InteractionResponseType::UpdateMessage)
+        .interaction_response_data(|d| d.content("Importing...").components(|c| c))).await?;
+
+    let guild_id = add_guild(ctx).await?;
+    let settings = imported.settings.get_guild_settings();
+    save_guild_settings(&guild_id, &settings, ctx.data()).await?;
+    for x in current.blocked_authors.iter().filter(|x| !imported.blocked_authors.contains(x)) {
+        ctx.data().database.remove_blocked_author(&guild_id, &x.character_name, &x.world).await?;
+    }
+    for x in imported.blocked_authors.iter().filter(|x| !current.blocked_authors.contains(x)) {
+        ctx.data().database.add_blocked_author(&guild_id, &x.character_name, &x.world).await?;
+    }
+    for x in current.blocked_keywords.iter().filter(|x| !imported.blocked_keywords.contains(x)) {
+        ctx.data().database.remove_blocked_keyword(&guild_id, &x.keyword, x.is_regex).await?;
+    }
+    for x in imported.blocked_keywords.iter().filter(|x| !current.blocked_keywords.contains(x)) {
+        ctx.data().database.add_blocked_keyword(&guild_id, &x.keyword, x.is_regex).await?;
+    }
+    ctx.data().guild_blocklists.lock().unwrap().remove(&guild_id);
+    let blocklist = get_guild_blocklist(&guild_id, ctx.data()).await;
+
+    let mut created = 0;
+    let mut failed = Vec::new();
+    for config in imported.boards.iter().filter(|x| !current.boards.iter().any(|y| y.is_same_board(x))) {
+        let channel = &channels[&serenity::ChannelId(config.channel_id.parse::<u64>()?)];
+        let mut board = get_imported_board(config, &settings, Arc::clone(&blocklist));
+        let result = match post_board(&mut board, channel, &config.include_filters, &config.exclude_filters, ctx.data(), &ctx.discord().http).await {
+            Ok(message_id) => match &config.template {
+                Some(template) => ctx.data().database.save_template(&message_id, template).await,
+                None => Ok(())
+            },
+            Err(e) => Err(e)
+        };
+        match result {
+            Ok(()) => created += 1,
+            Err(e) => {
+                println!("Couldn't import board {} in {}: {}", board.get_title(), channel.id, e);
+                failed.push(format!("{} in <#{}>", board.get_title(), channel.id));
+            }
+        }
+    }
+
+    let mut response = format!("Imported the settings and blocklist, and created {} boards.", created);
+    if !failed.is_empty() {
+        response += &format!(" Couldn't post {}, check the bot can send messages there.", failed.join(", "));
+    }
+    ctx.say(response).await?;
+    Ok(())
+}
+
+// Registers the slash commands along with their localizations, globally or with "guild" only in this server
 #[poise::command(owners_only, prefix_command, hide_in_help)]
-async fn register(ctx: Context<'_>) -> Result<(), Error> {
-    poise::builtins::register_application_commands_buttons(ctx).await?;
+async fn register(ctx: Context<'_>, #[flag] guild: bool) -> Result<(), Error> {
+    let mut commands = poise::builtins::create_application_commands(&ctx.framework().options().commands);
+    locale_util::localize_commands(&mut commands);
+    let command_count = commands.0.len();
+
+    if guild {
+        let guild_id = match ctx.guild_id() {
+            Some(x) => x,
+            None => {
+                ctx.say("Must be called in a server.").await?;
+                return Ok(());
+            }
+        };
+        guild_id.set_application_commands(ctx.discord(), |b| { *b = commands; b }).await?;
+        ctx.say(format!("Registered {} commands in this server.", command_count)).await?;
+    } else {
+        serenity::ApplicationCommand::set_global_application_commands(ctx.discord(), |b| { *b = commands; b }).await?;
+        ctx.say(format!("Registered {} commands globally.", command_count)).await?;
+    }
+    Ok(())
+}
+
+// Moves a paged board one page back or forward. Everyone looking at the message sees the same page.
+async fn turn_page(ctx: &serenity::Context, component: &serenity::MessageComponentInteraction, data: &Data) -> Result<(), Error> {
+    let message_id = component.message.id.0.to_string();
+    let board = match load_board(&message_id, data).await? {
+        Some(x) => x,
+        None => {
+            component.create_interaction_response(&ctx.http, |r| r.kind(serenity::InteractionResponseType::DeferredUpdateMessage)).await?;
+            return Ok(());
+        }
+    };
+
+    let mut pages = {
+        let pf_listings = get_listings(data);
+        let filtered_listings = filter_listings(&board, &pf_listings);
+        render_util::get_pages(&board, filtered_listings, &data.refresh_times.lock().unwrap(), board.overflow.get_max_pages())
+    };
+    let page_count = pages.len();
+    let page = board.message_row.page.unwrap_or(0) as usize;
+    let page = if component.data.custom_id == render_util::PAGE_PREVIOUS_ID { page.saturating_sub(1) } else { page + 1 };
+    let page = cmp::min(page, page_count - 1);
+    data.database.set_message_page(&message_id, page as i64).await?;
+
+    let embeds = pages.remove(page);
+    component.create_interaction_response(&ctx.http, |r| r.kind(serenity::InteractionResponseType::UpdateMessage)
+        .interaction_response_data(|d| d.set_embeds(embeds).set_components(render_util::get_page_buttons(page, page_count, board.locale)))).await?;
+    Ok(())
+}
+
+// Drops the cached filters and templates of boards that are gone
+fn forget_boards(message_ids: &[String], data: &Data) {
+    let mut description_filters = data.description_filters.lock().unwrap();
+    let mut board_templates = data.board_templates.lock().unwrap();
+    let mut payload_hashes = data.payload_hashes.lock().unwrap();
+    for message_id in message_ids {
+        description_filters.remove(message_id);
+        board_templates.remove(message_id);
+        payload_hashes.remove(message_id);
+    }
+}
+
+// A deleted board takes its extra messages with it, a deleted extra message is sent again on the next refresh
+async fn remove_deleted_message(message_id: serenity::MessageId, data: &Data, http: &Http) -> Result<(), Error> {
+    let message_id = message_id.0.to_string();
+    if data.database.delete_overflow_message_by_id(&message_id).await? {
+        return Ok(());
+    }
+    let mut board = match load_board(&message_id, data).await? {
+        Some(x) => x,
+        None => return Ok(())
+    };
+    if board.message_row.recreate_if_deleted.unwrap_or(0) == 1 {
+        return recreate_board(&mut board, data, http).await;
+    }
+    if let Err(e) = update_overflow_messages(&board, data, http, Vec::new()).await {
+        println!("Error deleting overflow messages of {}: {}.", message_id, e);
+    }
+    data.database.delete_message(&message_id).await?;
+    forget_boards(&[message_id.to_string()], data);
+    println!("Board {} was deleted, removed it from the db.", message_id);
+    Ok(())
+}
+
+async fn event_listener(ctx: &serenity::Context, event: &poise::Event<'_>, _framework: poise::FrameworkContext<'_, Data, Error>, data: &Data) -> Result<(), Error> {
+    match event {
+        poise::Event::InteractionCreate { interaction: serenity::Interaction::MessageComponent(component) } => {
+            if component.data.custom_id == render_util::PAGE_PREVIOUS_ID || component.data.custom_id == render_util::PAGE_NEXT_ID {
+                turn_page(ctx, component, data).await?;
+            }
+        }
+        // unavailable means an outage, the bot is still in the guild
+        poise::Event::GuildDelete { incomplete, .. } if !incomplete.unavailable => {
+            let guild_id = incomplete.id.0.to_string();
+            let message_ids = data.database.delete_guild(&guild_id).await?;
+            forget_boards(&message_ids, data);
+            data.guild_blocklists.lock().unwrap().remove(&guild_id);
+            println!("Left guild {}, removed it and its {} boards from the db.", guild_id, message_ids.len());
+        }
+        poise::Event::GuildUpdate { new_but_incomplete, .. } => {
+            data.database.set_guild_name(&new_but_incomplete.id.0.to_string(), &new_but_incomplete.name).await?;
+        }
+        poise::Event::ChannelDelete { channel } => {
+            let message_ids = data.database.delete_channel_messages(&channel.id.0.to_string()).await?;
+            if !message_ids.is_empty() {
+                forget_boards(&message_ids, data);
+                println!("Channel {} was deleted, removed its {} boards from the db.", channel.id, message_ids.len());
+            }
+        }
+        poise::Event::MessageDelete { deleted_message_id, .. } => {
+            remove_deleted_message(*deleted_message_id, data, &ctx.http).await?;
+        }
+        poise::Event::MessageDeleteBulk { multiple_deleted_messages_ids, .. } => {
+            for message_id in multiple_deleted_messages_ids {
+                remove_deleted_message(*message_id, data, &ctx.http).await?;
+            }
+        }
+        _ => {}
+    }
     Ok(())
 }
 
@@ -305,8 +1631,23 @@ async fn update_xivpfs_rustfn_aux(data: &Data) -> Result<(), Error> {
         .text()
         .await?;
 
-    let listings = scraper_util::get_listings(html);
-    *data.pf_listings.lock().unwrap() = listings;
+    let fetched_at = xiv_util::get_unix_time();
+    let mut listings = scraper_util::get_listings(html, fetched_at);
+    // the listings before the first fetch are samples, so nothing is new yet
+    if data.has_fetched.load(Ordering::SeqCst) {
+        let previous_ids = get_listings(data).iter().map(|x| x.id).collect::<HashSet<u64>>();
+        for listing in listings.iter_mut() {
+            listing.is_new = !previous_ids.contains(&listing.id);
+        }
+    }
+    // boards still get the new listings if the history can't be written
+    if let Err(e) = history_util::record_listings(data.database.as_ref(), &listings, fetched_at).await {
+        println!("Couldn't record listing history: {}", e);
+    }
+    *data.pf_listings.lock().unwrap() = Arc::new(listings);
+    data.refresh_times.lock().unwrap().fetched_at = fetched_at;
+    // set last, the board scheduler waits on it
+    data.has_fetched.store(true, Ordering::SeqCst);
     Ok(())
 }
 
@@ -324,23 +1665,33 @@ async fn update_xivpfs(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 async fn init_bot() {
-    let database = sqlx::sqlite::SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect_with(
-            sqlx::sqlite::SqliteConnectOptions::new()
-                .filename("database.sqlite")
-                .create_if_missing(true),
-        )
-        .await
-        .expect("Couldn't connect to database");
+    let database = storage_util::connect().await.expect("Couldn't connect to database");
+    database.migrate().await.expect("Couldn't migrate the database");
 
-    //sqlx::migrate!("./migrations").run(&database).await.expect("Couldn't run database migrations");
+    let history_database = Arc::clone(&database);
+    task::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(HISTORY_COMPACTION_INTERVAL_SECONDS));
+        loop {
+            interval.tick().await;
+            match history_util::compact_history(history_database.as_ref(), xiv_util::get_unix_time()).await {
+                Ok(deleted) => { println!("Compacted listing history, deleted {} old listings", deleted); }
+                Err(e) => { println!("Couldn't compact listing history: {}", e); }
+            }
+        }
+    });
 
-    let pf_listings = Mutex::new(scraper_util::get_sample_listings().await);
+    let pf_listings = Mutex::new(Arc::new(scraper_util::get_sample_listings().await));
+    let refresh_times = Mutex::new(render_util::RefreshTimes { fetched_at: xiv_util::get_unix_time() });
 
     let bot = Data {
         database,
-        pf_listings    
+        pf_listings,
+        has_fetched: AtomicBool::new(false),
+        refresh_times,
+        description_filters: Mutex::new(HashMap::new()),
+        guild_blocklists: Mutex::new(HashMap::new()),
+        board_templates: Mutex::new(HashMap::new()),
+        payload_hashes: Mutex::new(HashMap::new())
     };
 
     let token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
@@ -348,7 +1699,8 @@ async fn init_bot() {
 
     let framework = poise::Framework::build()
         .options(poise::FrameworkOptions {
-            commands: vec![display_xivpfs(), register()], //update_messages(), update_xivpfs(), update_message_sync()
+            commands: vec![display_xivpfs(), blocklist(), language(), template(), settings(), config(), register()], //update_messages(), update_xivpfs(), update_message_sync()
+            listener: |ctx, event, framework, data| Box::pin(event_listener(ctx, event, framework, data)),
             ..Default::default()
         })
         .token(token)
@@ -361,20 +1713,45 @@ async fn init_bot() {
     let cloned = Arc::clone(&framework);
 
 
+    // fetching xivpf and refreshing boards run apart, boards render whatever was fetched last
+    let scrape_framework = Arc::clone(&framework);
     task::spawn(async move {
-        let mut interval = time::interval(Duration::from_millis(5*60*1000));
-        let http = Arc::new(serenity::http::Http::new(&token_2));
-
+        let scrape_interval_seconds = schedule_util::get_scrape_interval_seconds();
+        let mut interval = time::interval(Duration::from_secs(scrape_interval_seconds as u64));
         loop {
-            match update_xivpfs_rustfn(Arc::clone(&framework)).await {
+            interval.tick().await;
+            match update_xivpfs_rustfn(Arc::clone(&scrape_framework)).await {
                 Ok(()) => {}
                 Err(e) => {println!("Couldn't update_xivpfs_rustfn {:?}", e)}
             }
-            match update_messages_rustfn(Arc::clone(&framework), Arc::clone(&http)).await {
-                Ok(sz) => {}
+        }
+    });
+
+    task::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(schedule_util::get_tick_seconds()));
+        // a long pass pushes the next one back instead of starting several back to back
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        let http = Arc::new(serenity::http::Http::new(&token_2));
+        let mut is_startup = true;
+
+        loop {
+            interval.tick().await;
+            // boards wait for real listings rather than showing the samples
+            if !framework.user_data().await.has_fetched.load(Ordering::SeqCst) {
+                continue;
+            }
+            match update_messages_rustfn(Arc::clone(&framework), Arc::clone(&http), is_startup).await {
+                Ok(report) => {
+                    if report.board_count > 0 {
+                        println!("{}", report);
+                    }
+                    if report.elapsed_ms > schedule_util::get_scrape_interval_seconds() * 1000 {
+                        println!("The refresh took longer than the {} second fetch interval.", schedule_util::get_scrape_interval_seconds());
+                    }
+                }
                 Err(e) => {println!("Couldn't update_messages_rustfn {:?}", e)}
             }
-            interval.tick().await;
+            is_startup = false;
         }
     });
 
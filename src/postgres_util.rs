@@ -0,0 +1,543 @@
+use crate::history_util;
+use crate::settings_util::GuildSettings;
+use crate::storage_util::{Error, MessageLists, MessageRow, Storage};
+use crate::template_util::TemplateSource;
+use crate::xiv_util::PFListing;
+use sqlx::migrate::Migrate;
+use sqlx::{FromRow, Row};
+use std::collections::HashMap;
+
+// The query! macros check against one database while compiling, which is SQLite, so these queries are checked when they run
+pub struct PostgresStorage {
+    pool: sqlx::PgPool
+}
+
+impl PostgresStorage {
+    pub async fn connect(url: &str) -> Result<PostgresStorage, Error> {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(5)
+            .connect(url)
+            .await?;
+        Ok(PostgresStorage { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for PostgresStorage {
+    // Backups are left to pg_dump, which doesn't belong in the bot
+    async fn migrate(&self) -> Result<(), Error> {
+        let migrator = sqlx::migrate!("./migrations/postgres");
+        let applied = {
+            let mut connection = self.pool.acquire().await?;
+            connection.ensure_migrations_table().await?;
+            connection.list_applied_migrations().await?
+        };
+        let pending = crate::storage_util::get_pending_migrations(&migrator, &applied)?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        migrator.run(&self.pool).await?;
+        println!("Applied {} database migrations: {}", pending.len(), pending.join(", "));
+        Ok(())
+    }
+
+    async fn add_guild(&self, guild_id: &str, guild_name: &str) -> Result<(), Error> {
+        sqlx::query("INSERT INTO guilds(guild_id, guild_name) VALUES($1, $2) ON CONFLICT DO NOTHING")
+            .bind(guild_id)
+            .bind(guild_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_guild_name(&self, guild_id: &str, guild_name: &str) -> Result<(), Error> {
+        sqlx::query("UPDATE guilds SET guild_name=$1 WHERE guild_id=$2")
+            .bind(guild_name)
+            .bind(guild_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_guild(&self, guild_id: &str) -> Result<Vec<String>, Error> {
+        let mut transaction = self.pool.begin().await?;
+        let message_ids = sqlx::query_scalar::<_, String>("DELETE FROM messages WHERE guild_id=$1 RETURNING message_id")
+            .bind(guild_id)
+            .fetch_all(&mut transaction)
+            .await?;
+        for query in ["DELETE FROM guild_blocked_authors WHERE guild_id=$1", "DELETE FROM guild_blocked_keywords WHERE guild_id=$1", "DELETE FROM guilds WHERE guild_id=$1"] {
+            sqlx::query(query)
+                .bind(guild_id)
+                .execute(&mut transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(message_ids)
+    }
+
+    async fn get_guild_settings(&self, guild_id: &str) -> Result<Option<GuildSettings>, Error> {
+        Ok(sqlx::query_as::<_, GuildSettings>("SELECT data_center, locale, timezone, manager_role_id, layout, allow_statics, include_filter, exclude_filter, log_channel_id FROM guild_settings WHERE guild_id=$1")
+            .bind(guild_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn get_all_guild_settings(&self) -> Result<HashMap<String, GuildSettings>, Error> {
+        let rows = sqlx::query("SELECT guild_id, data_center, locale, timezone, manager_role_id, layout, allow_statics, include_filter, exclude_filter, log_channel_id FROM guild_settings")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut settings = HashMap::new();
+        for row in rows {
+            settings.insert(row.try_get("guild_id")?, GuildSettings::from_row(&row)?);
+        }
+        Ok(settings)
+    }
+
+    async fn save_guild_settings(&self, guild_id: &str, settings: &GuildSettings) -> Result<(), Error> {
+        sqlx::query("INSERT INTO guild_settings(guild_id, data_center, locale, timezone, manager_role_id, layout, allow_statics, include_filter, exclude_filter, log_channel_id) VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT(guild_id) DO UPDATE SET data_center=excluded.data_center, locale=excluded.locale, timezone=excluded.timezone, manager_role_id=excluded.manager_role_id,
+            layout=excluded.layout, allow_statics=excluded.allow_statics, include_filter=excluded.include_filter, exclude_filter=excluded.exclude_filter,
+            log_channel_id=excluded.log_channel_id")
+            .bind(guild_id)
+            .bind(&settings.data_center)
+            .bind(&settings.locale)
+            .bind(&settings.timezone)
+            .bind(&settings.manager_role_id)
+            .bind(&settings.layout)
+            .bind(settings.allow_statics)
+            .bind(&settings.include_filter)
+            .bind(&settings.exclude_filter)
+            .bind(&settings.log_channel_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_blocked_authors(&self, guild_id: &str) -> Result<Vec<(String, String)>, Error> {
+        Ok(sqlx::query_as::<_, (String, String)>("SELECT character_name, world FROM guild_blocked_authors WHERE guild_id=$1 ORDER BY character_name")
+            .bind(guild_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn add_blocked_author(&self, guild_id: &str, character_name: &str, world: &str) -> Result<(), Error> {
+        sqlx::query("INSERT INTO guild_blocked_authors(guild_id, character_name, world) VALUES($1, $2, $3) ON CONFLICT DO NOTHING")
+            .bind(guild_id)
+            .bind(character_name)
+            .bind(world)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_blocked_author(&self, guild_id: &str, character_name: &str, world: &str) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM guild_blocked_authors WHERE guild_id=$1 AND LOWER(character_name)=LOWER($2) AND LOWER(world)=LOWER($3)")
+            .bind(guild_id)
+            .bind(character_name)
+            .bind(world)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_blocked_keywords(&self, guild_id: &str) -> Result<Vec<(String, bool)>, Error> {
+        Ok(sqlx::query_as::<_, (String, i64)>("SELECT keyword, is_regex FROM guild_blocked_keywords WHERE guild_id=$1 ORDER BY keyword")
+            .bind(guild_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|(keyword, is_regex)| (keyword, is_regex == 1)).collect())
+    }
+
+    async fn add_blocked_keyword(&self, guild_id: &str, keyword: &str, is_regex: bool) -> Result<(), Error> {
+        sqlx::query("INSERT INTO guild_blocked_keywords(guild_id, keyword, is_regex) VALUES($1, $2, $3) ON CONFLICT DO NOTHING")
+            .bind(guild_id)
+            .bind(keyword)
+            .bind(if is_regex {1i64} else {0i64})
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_blocked_keyword(&self, guild_id: &str, keyword: &str, is_regex: bool) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM guild_blocked_keywords WHERE guild_id=$1 AND keyword=$2 AND is_regex=$3")
+            .bind(guild_id)
+            .bind(keyword)
+            .bind(if is_regex { 1i64 } else { 0 })
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_message_rows(&self) -> Result<Vec<MessageRow>, Error> {
+        Ok(sqlx::query_as::<_, MessageRow>("SELECT message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, page, error_count, last_error, last_error_at, quarantined_at, recreate_if_deleted, payload_hash, payload_edited_at, refresh_minutes, next_refresh_at FROM messages")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn get_guild_message_rows(&self, guild_id: &str) -> Result<Vec<MessageRow>, Error> {
+        Ok(sqlx::query_as::<_, MessageRow>("SELECT message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, page, error_count, last_error, last_error_at, quarantined_at, recreate_if_deleted, payload_hash, payload_edited_at, refresh_minutes, next_refresh_at FROM messages WHERE guild_id=$1")
+            .bind(guild_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn get_message_row(&self, message_id: &str) -> Result<Option<MessageRow>, Error> {
+        Ok(sqlx::query_as::<_, MessageRow>("SELECT message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, page, error_count, last_error, last_error_at, quarantined_at, recreate_if_deleted, payload_hash, payload_edited_at, refresh_minutes, next_refresh_at FROM messages WHERE message_id=$1")
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn get_all_message_lists(&self) -> Result<HashMap<String, MessageLists>, Error> {
+        let mut lists: HashMap<String, MessageLists> = HashMap::new();
+        for (message_id, duty_name) in sqlx::query_as::<_, (String, String)>("SELECT message_id, duty_name FROM message_duties ORDER BY id").fetch_all(&self.pool).await? {
+            lists.entry(message_id).or_default().duty_names.push(duty_name);
+        }
+        for (message_id, data_center) in sqlx::query_as::<_, (String, String)>("SELECT message_id, data_center FROM message_data_centers ORDER BY id").fetch_all(&self.pool).await? {
+            lists.entry(message_id).or_default().data_centers.push(data_center);
+        }
+        for (message_id, category) in sqlx::query_as::<_, (String, String)>("SELECT message_id, category FROM message_categories ORDER BY id").fetch_all(&self.pool).await? {
+            lists.entry(message_id).or_default().categories.push(category);
+        }
+        Ok(lists)
+    }
+
+    async fn get_message_lists(&self, message_id: &str) -> Result<MessageLists, Error> {
+        let mut values = Vec::new();
+        for query in ["SELECT duty_name FROM message_duties WHERE message_id=$1 ORDER BY id",
+            "SELECT data_center FROM message_data_centers WHERE message_id=$1 ORDER BY id",
+            "SELECT category FROM message_categories WHERE message_id=$1 ORDER BY id"] {
+            values.push(sqlx::query_scalar::<_, String>(query)
+                .bind(message_id)
+                .fetch_all(&self.pool)
+                .await?);
+        }
+        let categories = values.pop().unwrap_or_default();
+        let data_centers = values.pop().unwrap_or_default();
+        let duty_names = values.pop().unwrap_or_default();
+        Ok(MessageLists { duty_names, data_centers, categories })
+    }
+
+    async fn get_description_filters(&self, message_id: &str) -> Result<Vec<(String, bool)>, Error> {
+        Ok(sqlx::query_as::<_, (String, i64)>("SELECT pattern, is_exclude FROM message_description_filters WHERE message_id=$1 ORDER BY id")
+            .bind(message_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|(pattern, is_exclude)| (pattern, is_exclude != 0)).collect())
+    }
+
+    async fn create_message(&self, message_row: &MessageRow, lists: &MessageLists, include_patterns: &[String], exclude_patterns: &[String]) -> Result<(), Error> {
+        let message_id = &message_row.message_id;
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO messages(message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, recreate_if_deleted, refresh_minutes) VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)")
+            .bind(&message_row.message_id)
+            .bind(&message_row.channel_id)
+            .bind(&message_row.guild_id)
+            .bind(&message_row.data_center)
+            .bind(&message_row.duty_name)
+            .bind(message_row.allow_statics)
+            .bind(message_row.is_news)
+            .bind(message_row.filter_case_sensitive)
+            .bind(&message_row.sort_mode)
+            .bind(&message_row.party_type)
+            .bind(message_row.min_prog_phase)
+            .bind(&message_row.layout)
+            .bind(&message_row.overflow)
+            .bind(message_row.recreate_if_deleted)
+            .bind(message_row.refresh_minutes)
+            .execute(&mut transaction)
+            .await?;
+        for (query, values) in [("INSERT INTO message_duties(message_id, duty_name) VALUES($1, $2)", &lists.duty_names),
+            ("INSERT INTO message_categories(message_id, category) VALUES($1, $2)", &lists.categories),
+            ("INSERT INTO message_data_centers(message_id, data_center) VALUES($1, $2)", &lists.data_centers)] {
+            for value in values {
+                sqlx::query(query)
+                    .bind(message_id)
+                    .bind(value)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+        }
+        for (patterns, is_exclude) in [(include_patterns, 0i64), (exclude_patterns, 1i64)] {
+            for pattern in patterns {
+                sqlx::query("INSERT INTO message_description_filters(message_id, pattern, is_exclude) VALUES($1, $2, $3)")
+                    .bind(message_id)
+                    .bind(pattern)
+                    .bind(is_exclude)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_message(&self, message_id: &str) -> Result<(), Error> {
+        sqlx::query("DELETE FROM messages WHERE message_id=$1")
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_channel_messages(&self, channel_id: &str) -> Result<Vec<String>, Error> {
+        Ok(sqlx::query_scalar::<_, String>("DELETE FROM messages WHERE channel_id=$1 RETURNING message_id")
+            .bind(channel_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn is_guild_message(&self, message_id: &str, guild_id: &str) -> Result<bool, Error> {
+        let row = sqlx::query("SELECT message_id FROM messages WHERE message_id=$1 AND guild_id=$2")
+            .bind(message_id)
+            .bind(guild_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn set_message_page(&self, message_id: &str, page: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE messages SET page=$1 WHERE message_id=$2")
+            .bind(page)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_message_error(&self, message_id: &str, error: &str, at: i64) -> Result<i64, Error> {
+        let error_count = sqlx::query_scalar::<_, i64>("UPDATE messages SET error_count=COALESCE(error_count, 0)+1, last_error=$1, last_error_at=$2 WHERE message_id=$3 RETURNING error_count")
+            .bind(error)
+            .bind(at)
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(error_count.unwrap_or(0))
+    }
+
+    async fn quarantine_message(&self, message_id: &str, at: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE messages SET quarantined_at=$1 WHERE message_id=$2")
+            .bind(at)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn clear_message_errors(&self, message_id: &str) -> Result<(), Error> {
+        sqlx::query("UPDATE messages SET error_count=NULL, last_error=NULL, last_error_at=NULL, quarantined_at=NULL WHERE message_id=$1")
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_payload_hash(&self, message_id: &str, payload_hash: &str, edited_at: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE messages SET payload_hash=$1, payload_edited_at=$2 WHERE message_id=$3")
+            .bind(payload_hash)
+            .bind(edited_at)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_next_refresh_times(&self, times: &[(String, i64)]) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await?;
+        for (message_id, next_refresh_at) in times {
+            sqlx::query("UPDATE messages SET next_refresh_at=$1 WHERE message_id=$2")
+                .bind(next_refresh_at)
+                .bind(message_id)
+                .execute(&mut transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn replace_message_id(&self, message_id: &str, new_message_id: &str) -> Result<(), Error> {
+        // the other tables point at message_id, so the new row goes in before they move over
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO messages(message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, recreate_if_deleted, refresh_minutes)
+            SELECT $1, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, recreate_if_deleted, refresh_minutes
+            FROM messages WHERE message_id=$2")
+            .bind(new_message_id)
+            .bind(message_id)
+            .execute(&mut transaction)
+            .await?;
+        for table in ["message_duties", "message_data_centers", "message_categories", "message_description_filters", "message_overflow", "message_templates"] {
+            sqlx::query(&format!("UPDATE {} SET message_id=$1 WHERE message_id=$2", table))
+                .bind(new_message_id)
+                .bind(message_id)
+                .execute(&mut transaction)
+                .await?;
+        }
+        sqlx::query("DELETE FROM messages WHERE message_id=$1")
+            .bind(message_id)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_overflow_messages(&self, message_id: &str) -> Result<Vec<(i64, String)>, Error> {
+        Ok(sqlx::query_as::<_, (i64, String)>("SELECT position, overflow_message_id FROM message_overflow WHERE message_id=$1 ORDER BY position")
+            .bind(message_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn save_overflow_message(&self, message_id: &str, position: i64, overflow_message_id: &str) -> Result<(), Error> {
+        sqlx::query("INSERT INTO message_overflow(message_id, position, overflow_message_id) VALUES($1, $2, $3)
+            ON CONFLICT(message_id, position) DO UPDATE SET overflow_message_id=excluded.overflow_message_id")
+            .bind(message_id)
+            .bind(position)
+            .bind(overflow_message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_overflow_message(&self, message_id: &str, position: i64) -> Result<(), Error> {
+        sqlx::query("DELETE FROM message_overflow WHERE message_id=$1 AND position=$2")
+            .bind(message_id)
+            .bind(position)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_overflow_message_by_id(&self, overflow_message_id: &str) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM message_overflow WHERE overflow_message_id=$1")
+            .bind(overflow_message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_template(&self, message_id: &str) -> Result<Option<TemplateSource>, Error> {
+        Ok(sqlx::query_as::<_, TemplateSource>("SELECT title, body, footer, color FROM message_templates WHERE message_id=$1")
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn save_template(&self, message_id: &str, source: &TemplateSource) -> Result<(), Error> {
+        sqlx::query("INSERT INTO message_templates(message_id, title, body, footer, color) VALUES($1, $2, $3, $4, $5)
+            ON CONFLICT(message_id) DO UPDATE SET title=excluded.title, body=excluded.body, footer=excluded.footer, color=excluded.color")
+            .bind(message_id)
+            .bind(&source.title)
+            .bind(&source.body)
+            .bind(&source.footer)
+            .bind(&source.color)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_template(&self, message_id: &str) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM message_templates WHERE message_id=$1")
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_listings(&self, listings: &[PFListing], fetched_at: i64) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await?;
+        let open_listings = sqlx::query_as::<_, (i64, i64, i64, String, i64)>("SELECT listing_id, filled_count, slot_count, description, last_expires_at FROM listing_history WHERE outcome IS NULL")
+            .fetch_all(&mut transaction)
+            .await?
+            .into_iter()
+            .map(|(listing_id, filled_count, slot_count, description, last_expires_at)| (listing_id, history_util::OpenListing { filled_count, slot_count, description, last_expires_at }))
+            .collect::<HashMap<i64, history_util::OpenListing>>();
+        let changes = history_util::get_changes(open_listings, listings, fetched_at);
+
+        for seen in changes.seen {
+            let listing = seen.listing;
+            if seen.is_open {
+                sqlx::query("UPDATE listing_history SET last_seen_at=$1, last_expires_at=$2, flags=$3, filled_count=$4, slot_count=$5, description=$6 WHERE listing_id=$7")
+                    .bind(fetched_at)
+                    .bind(listing.expires_at)
+                    .bind(&listing.flags)
+                    .bind(seen.filled_count)
+                    .bind(seen.slot_count)
+                    .bind(&listing.description)
+                    .bind(seen.listing_id)
+                    .execute(&mut transaction)
+                    .await?;
+            } else {
+                // a listing that comes back under the same id picks up where it left off
+                sqlx::query("INSERT INTO listing_history(listing_id, duty_name, data_center, pf_category, character_name, world, flags, slot_count, filled_count, description, first_seen_at, last_seen_at, last_expires_at)
+                    VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $11, $12)
+                    ON CONFLICT(listing_id) DO UPDATE SET outcome=NULL, ended_at=NULL, flags=excluded.flags, slot_count=excluded.slot_count, filled_count=excluded.filled_count,
+                    description=excluded.description, last_seen_at=excluded.last_seen_at, last_expires_at=excluded.last_expires_at")
+                    .bind(seen.listing_id)
+                    .bind(&listing.title)
+                    .bind(&listing.data_center)
+                    .bind(&listing.pf_category)
+                    .bind(&listing.character_name)
+                    .bind(&listing.world)
+                    .bind(&listing.flags)
+                    .bind(seen.slot_count)
+                    .bind(seen.filled_count)
+                    .bind(&listing.description)
+                    .bind(fetched_at)
+                    .bind(listing.expires_at)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+            if seen.fill_changed {
+                sqlx::query("INSERT INTO listing_fill_events(listing_id, seen_at, filled_count, slot_count) VALUES($1, $2, $3, $4)
+                    ON CONFLICT(listing_id, seen_at) DO UPDATE SET filled_count=excluded.filled_count, slot_count=excluded.slot_count")
+                    .bind(seen.listing_id)
+                    .bind(fetched_at)
+                    .bind(seen.filled_count)
+                    .bind(seen.slot_count)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+            if seen.description_changed {
+                sqlx::query("INSERT INTO listing_descriptions(listing_id, seen_at, description) VALUES($1, $2, $3)
+                    ON CONFLICT(listing_id, seen_at) DO UPDATE SET description=excluded.description")
+                    .bind(seen.listing_id)
+                    .bind(fetched_at)
+                    .bind(&listing.description)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+        }
+
+        for (listing_id, outcome) in changes.ended {
+            sqlx::query("UPDATE listing_history SET outcome=$1, ended_at=$2 WHERE listing_id=$3")
+                .bind(outcome.to_db_string())
+                .bind(fetched_at)
+                .bind(listing_id)
+                .execute(&mut transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn compact_history(&self, delete_before: i64, compact_before: i64) -> Result<u64, Error> {
+        let deleted = sqlx::query("DELETE FROM listing_history WHERE ended_at < $1")
+            .bind(delete_before)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        sqlx::query("DELETE FROM listing_fill_events AS e WHERE e.listing_id IN (SELECT listing_id FROM listing_history WHERE ended_at < $1)
+            AND e.seen_at > (SELECT MIN(seen_at) FROM listing_fill_events WHERE listing_id=e.listing_id)
+            AND e.seen_at < (SELECT MAX(seen_at) FROM listing_fill_events WHERE listing_id=e.listing_id)")
+            .bind(compact_before)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM listing_descriptions AS d WHERE d.listing_id IN (SELECT listing_id FROM listing_history WHERE ended_at < $1)
+            AND d.seen_at > (SELECT MIN(seen_at) FROM listing_descriptions WHERE listing_id=d.listing_id)
+            AND d.seen_at < (SELECT MAX(seen_at) FROM listing_descriptions WHERE listing_id=d.listing_id)")
+            .bind(compact_before)
+            .execute(&self.pool)
+            .await?;
+        Ok(deleted)
+    }
+}
@@ -0,0 +1,276 @@
+use regex::{Regex, RegexBuilder};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+#[derive(poise::ChoiceParameter)]
+pub enum PartyType {
+    Prog,
+    Clear,
+    Reclear,
+    Farm,
+    Learning
+}
+
+impl PartyType {
+    pub fn to_db_string(&self) -> &'static str {
+        match self {
+            PartyType::Prog => "prog",
+            PartyType::Clear => "clear",
+            PartyType::Reclear => "reclear",
+            PartyType::Farm => "farm",
+            PartyType::Learning => "learning"
+        }
+    }
+
+    pub fn from_db_string(input: &str) -> Option<PartyType> {
+        match input {
+            "prog" => Some(PartyType::Prog),
+            "clear" => Some(PartyType::Clear),
+            "reclear" => Some(PartyType::Reclear),
+            "farm" => Some(PartyType::Farm),
+            "learning" => Some(PartyType::Learning),
+            _ => None
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            PartyType::Prog => "Prog",
+            PartyType::Clear => "Clear",
+            PartyType::Reclear => "Reclear",
+            PartyType::Farm => "Farm",
+            PartyType::Learning => "Learning"
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct ProgPoint {
+    pub phase: u32,
+    pub mechanic: Option<&'static str>
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Default)]
+pub struct ProgTag {
+    pub prog_point: Option<ProgPoint>,
+    pub party_types: Vec<PartyType>
+}
+
+impl ProgTag {
+    // e.g. "[P6 Wroth Flames][Reclear]", empty if nothing was recognized
+    pub fn to_display_string(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(prog_point) = &self.prog_point {
+            match prog_point.mechanic {
+                Some(mechanic) => parts.push(format!("P{} {}", prog_point.phase, mechanic)),
+                None => parts.push(format!("P{}", prog_point.phase))
+            }
+        }
+        for party_type in &self.party_types {
+            // a prog point already says it's a prog party
+            if *party_type == PartyType::Prog && self.prog_point.is_some() {
+                continue;
+            }
+            parts.push(party_type.get_name().to_string());
+        }
+        parts.iter().map(|x| format!("[{}]", x)).collect::<Vec<String>>().join("")
+    }
+
+    pub fn get_phase(&self) -> u32 {
+        self.prog_point.as_ref().map(|x| x.phase).unwrap_or(0)
+    }
+}
+
+// (phase, mechanic, pattern, short aliases). Later entries win over earlier ones in the same phase.
+// Short aliases are two letter abbreviations that are also ordinary words, so they only count next to prog context.
+const UCOB_VOCABULARY: &[(u32, &str, &str, &str)] = &[
+    (1, "Twintania", r"twin(tania)?|twisters?", ""),
+    (2, "Nael", r"nael", ""),
+    (3, "Quickmarch", r"quick ?march", ""),
+    (3, "Blackfire", r"blackfire", ""),
+    (3, "Fellruin", r"fell ?ruin", ""),
+    (3, "Heavensfall", r"heavensfall", ""),
+    (3, "Tenstrike", r"ten ?strike", ""),
+    (3, "Grand Octet", r"octet", ""),
+    (4, "Adds", r"adds|triple threat", ""),
+    (5, "Golden Bahamut", r"golden|teraflare", "gb")
+];
+
+const UWU_VOCABULARY: &[(u32, &str, &str, &str)] = &[
+    (1, "Garuda", r"\w*ruda", ""),
+    (2, "Ifrit", r"ifrit|infrit|nails", ""),
+    (3, "Titan", r"titan\w*|gaols?", ""),
+    (4, "Ultima", r"ultima|lahabrea|predation|annihilation|anni|suppression|(primal )?roulette", "")
+];
+
+const TEA_VOCABULARY: &[(u32, &str, &str, &str)] = &[
+    (1, "Living Liquid", r"living liquid|liquid|cascade", "ll"),
+    (1, "Limit Cut", r"limit cut", "lc"),
+    (2, "BJ/CC", r"bj|bjcc|brute justice|cruise chaser", "cc"),
+    (2, "Nisi", r"nisi", ""),
+    (3, "Alexander Prime", r"alex(ander)? prime|inception|wormhole", "wh"),
+    (4, "Perfect Alexander", r"perfect alex(ander)?|fate calibration|calibration", "pa")
+];
+
+const DSR_VOCABULARY: &[(u32, &str, &str, &str)] = &[
+    (1, "Adelphel", r"adel+phel|door( boss)?", ""),
+    (2, "Thordan", r"thordan", ""),
+    (2, "Strength of the Ward", r"strength( of the ward)?|sotw", ""),
+    (2, "Sanctity of the Ward", r"sanctity( of the ward)?|sanc", ""),
+    (3, "Nidhogg", r"nidhogg|nidstinien|nid", ""),
+    (3, "Dive from Grace", r"dive from grace|dfg", ""),
+    (4, "Eyes", r"eyes|mirage dive", ""),
+    (5, "Intermission", r"intermission|rewind|haurchefant|alt(ernate)? (timeline|end)", ""),
+    (5, "Wrath of the Heavens", r"wrath( of the heavens)?", ""),
+    (5, "Death of the Heavens", r"death of the heavens|doth", ""),
+    (6, "Double Dragons", r"double dragons|hraesvelgr", "dd"),
+    (6, "Wroth Flames", r"wroth( flames)?", ""),
+    (6, "Akh Afah", r"akh ?afah", ""),
+    (6, "Hallowed Wings", r"hallowed( wings)?", ""),
+    (6, "Cauterize", r"cauterize|caut", ""),
+    (7, "Dragon-King Thordan", r"dragon.?king( thordan)?|dkt", ""),
+    (7, "Exaflares", r"exa(flare)?s?", ""),
+    (7, "Trinity", r"trinity", "")
+];
+
+fn get_vocabulary(duty_name: &str) -> &'static [(u32, &'static str, &'static str, &'static str)] {
+    match duty_name {
+        "The Unending Coil of Bahamut (Ultimate)" => UCOB_VOCABULARY,
+        "The Weapon's Refrain (Ultimate)" => UWU_VOCABULARY,
+        "The Epic of Alexander (Ultimate)" => TEA_VOCABULARY,
+        "Dragonsong's Reprise (Ultimate)" => DSR_VOCABULARY,
+        _ => &[]
+    }
+}
+
+fn build_regex(pattern: &str) -> Regex {
+    RegexBuilder::new(&format!(r"\b(?:{})\b", pattern)).case_insensitive(true).build().unwrap()
+}
+
+// Words that show a short alias is meant as a prog point, e.g. "PA prog", "prog to LC", "cp run"
+const ALIAS_CONTEXT: &str = r"prog\w*|to|till|until|past|at|from|clears?|clearing|re-?clears?|run|party|p[1-9]|phase ?[1-9]";
+
+// Matches the aliases only with a context word at most one word before or after them
+fn build_alias_regex(aliases: &str) -> Option<Regex> {
+    if aliases.is_empty() {
+        return None;
+    }
+    Some(build_regex(&format!(r"(?:{context})\W+(?:\w+\W+)?(?:{aliases})|(?:{aliases})\W+(?:\w+\W+)?(?:{context})", context = ALIAS_CONTEXT, aliases = aliases)))
+}
+
+fn is_match(re: &Regex, alias_re: &Option<Regex>, description: &str) -> bool {
+    re.is_match(description) || alias_re.as_ref().map(|x| x.is_match(description)).unwrap_or(false)
+}
+
+lazy_static! {
+    static ref PHASE_RE: Regex = build_regex(r"(?:p|phase ?)([1-9])");
+    static ref PARTY_TYPE_RES: Vec<(PartyType, Regex, Option<Regex>)> = vec![
+        (PartyType::Prog, build_regex(r"prog\w*|clean ?up"), None),
+        (PartyType::Clear, build_regex(r"clears?|clearing|c4[0-9]|clear for [0-9]"), build_alias_regex("cp")),
+        (PartyType::Reclear, build_regex(r"re-?clears?|weekly"), None),
+        (PartyType::Farm, build_regex(r"farm\w*|[0-9] ?chests?|totems?|mounts?"), None),
+        (PartyType::Learning, build_regex(r"learn\w*|fresh|blind|first time"), None)
+    ];
+    static ref VOCABULARY_RES: HashMap<&'static str, Vec<(u32, &'static str, Regex, Option<Regex>)>> = {
+        ["The Unending Coil of Bahamut (Ultimate)", "The Weapon's Refrain (Ultimate)", "The Epic of Alexander (Ultimate)", "Dragonsong's Reprise (Ultimate)"].iter()
+            .map(|duty_name| (*duty_name, get_vocabulary(duty_name).iter()
+                .map(|(phase, mechanic, pattern, aliases)| (*phase, *mechanic, build_regex(pattern), build_alias_regex(aliases)))
+                .collect()))
+            .collect()
+    };
+}
+
+// Tags a listing with the furthest prog point its description mentions, and what kind of party it is.
+pub fn get_prog_tag(duty_name: &str, description: &str) -> ProgTag {
+    let mut prog_point: Option<ProgPoint> = None;
+
+    for captures in PHASE_RE.captures_iter(description) {
+        let phase = captures[1].parse::<u32>().unwrap();
+        if prog_point.as_ref().map(|x| phase > x.phase).unwrap_or(true) {
+            prog_point = Some(ProgPoint { phase, mechanic: None });
+        }
+    }
+
+    if let Some(vocabulary) = VOCABULARY_RES.get(duty_name) {
+        for (phase, mechanic, re, alias_re) in vocabulary {
+            if is_match(re, alias_re, description) && prog_point.as_ref().map(|x| *phase >= x.phase).unwrap_or(true) {
+                prog_point = Some(ProgPoint { phase: *phase, mechanic: Some(mechanic) });
+            }
+        }
+    }
+
+    let mut party_types = PARTY_TYPE_RES.iter()
+        .filter(|(_, re, alias_re)| is_match(re, alias_re, description))
+        .map(|(party_type, _, _)| *party_type)
+        .collect::<Vec<PartyType>>();
+    // "re-clear" also matches the clear pattern
+    if party_types.contains(&PartyType::Reclear) {
+        party_types.retain(|x| *x != PartyType::Clear);
+    }
+    if prog_point.is_some() && party_types.is_empty() {
+        party_types.push(PartyType::Prog);
+    }
+
+    ProgTag { prog_point, party_types }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEA: &str = "The Epic of Alexander (Ultimate)";
+    const DSR: &str = "Dragonsong's Reprise (Ultimate)";
+
+    fn get_mechanic(duty_name: &str, description: &str) -> Option<&'static str> {
+        get_prog_tag(duty_name, description).prog_point.and_then(|x| x.mechanic)
+    }
+
+    #[test]
+    fn short_aliases_match_next_to_prog_context() {
+        assert_eq!(get_mechanic(TEA, "PA prog, know wormhole"), Some("Perfect Alexander"));
+        assert_eq!(get_mechanic(TEA, "prog to LC"), Some("Limit Cut"));
+        assert_eq!(get_mechanic(TEA, "BJ/CC: CC prog"), Some("BJ/CC"));
+        assert_eq!(get_mechanic(DSR, "DD prog, be on time"), Some("Double Dragons"));
+        assert_eq!(get_prog_tag(DSR, "cp run, know the fight").party_types, vec![PartyType::Clear]);
+    }
+
+    #[test]
+    fn short_aliases_need_context() {
+        assert_eq!(get_mechanic(TEA, "pa hola, cc me on discord"), None);
+        assert_eq!(get_mechanic(TEA, "LL LC WH"), None);
+        assert_eq!(get_mechanic(DSR, "dd"), None);
+        assert!(get_prog_tag(DSR, "cp").party_types.is_empty());
+    }
+
+    #[test]
+    fn short_aliases_need_word_boundaries() {
+        assert_eq!(get_mechanic(TEA, "prog to a paladin-friendly spot"), None);
+        assert_eq!(get_mechanic(DSR, "prog to addition"), None);
+    }
+
+    #[test]
+    fn aliases_only_count_for_their_duty() {
+        assert_eq!(get_mechanic(DSR, "PA prog"), None);
+        assert_eq!(get_mechanic(TEA, "DD prog"), None);
+    }
+
+    #[test]
+    fn furthest_prog_point_wins() {
+        let tag = get_prog_tag(DSR, "P5 prog, P6 wroth flames, reclear welcome");
+        assert_eq!(tag.prog_point, Some(ProgPoint { phase: 6, mechanic: Some("Wroth Flames") }));
+        assert_eq!(tag.party_types, vec![PartyType::Prog, PartyType::Reclear]);
+    }
+
+    #[test]
+    fn long_patterns_still_match_without_context() {
+        assert_eq!(get_mechanic(TEA, "perfect alexander"), Some("Perfect Alexander"));
+        assert_eq!(get_prog_tag(TEA, "clear party").party_types, vec![PartyType::Clear]);
+    }
+}
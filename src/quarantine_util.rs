@@ -0,0 +1,50 @@
+use crate::storage_util::MessageRow;
+
+// Discord's json error codes for the failures a board can run into
+pub const UNKNOWN_CHANNEL: isize = 10003;
+pub const UNKNOWN_MESSAGE: isize = 10008;
+const MISSING_ACCESS: isize = 50001;
+const MISSING_PERMISSIONS: isize = 50013;
+
+// Failed refreshes in a row before a board is quarantined
+pub fn get_errors_before_quarantine() -> i64 {
+    std::env::var("QUARANTINE_AFTER_ERRORS").unwrap_or("3".to_string()).parse::<i64>().unwrap()
+}
+
+// How often a quarantined board is tried again
+fn get_retry_minutes() -> i64 {
+    std::env::var("QUARANTINE_RETRY_MINUTES").unwrap_or("60".to_string()).parse::<i64>().unwrap()
+}
+
+// Quarantined boards are only refreshed once in a while, to see whether the bot got its access back
+pub fn is_due(message_row: &MessageRow, now: i64) -> bool {
+    match (message_row.quarantined_at, message_row.last_error_at) {
+        (Some(_), Some(last_error_at)) => now - last_error_at >= get_retry_minutes() * 60,
+        _ => true
+    }
+}
+
+// Why Discord turned the bot away, for the log channel
+pub fn get_reason(status: u16, code: Option<isize>) -> String {
+    match code {
+        Some(UNKNOWN_CHANNEL) => "its channel is gone".to_string(),
+        Some(UNKNOWN_MESSAGE) => "its message was deleted".to_string(),
+        Some(MISSING_ACCESS) => "the bot can't see its channel".to_string(),
+        Some(MISSING_PERMISSIONS) => "the bot is missing permissions in its channel".to_string(),
+        _ => format!("Discord answered with status {}", status)
+    }
+}
+
+pub fn get_quarantine_notice(title: &str, channel_id: &str, reason: &str, error_count: i64) -> String {
+    format!("The board **{}** in <#{}> stopped updating after {} failed refreshes: {}. \
+        The bot keeps checking it every {} minutes and picks it up again once it can.",
+        title, channel_id, error_count, reason, get_retry_minutes())
+}
+
+pub fn get_recovery_notice(title: &str, channel_id: &str) -> String {
+    format!("The board **{}** in <#{}> is updating again.", title, channel_id)
+}
+
+pub fn get_recreated_notice(title: &str, channel_id: &str) -> String {
+    format!("The board **{}** in <#{}> was deleted, so the bot posted it again.", title, channel_id)
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+// Channels refreshed at once. Boards in the same channel share Discord's rate limit bucket, so they go one after another.
+pub fn get_refresh_concurrency() -> usize {
+    std::env::var("REFRESH_CONCURRENCY").unwrap_or("8".to_string()).parse::<usize>().unwrap().max(1)
+}
+
+// Boards that didn't change are still edited this often, which also catches a board deleted while the bot was offline
+pub fn get_max_unchanged_minutes() -> i64 {
+    std::env::var("MAX_UNCHANGED_MINUTES").unwrap_or("15".to_string()).parse::<i64>().unwrap()
+}
+
+// Whether a board can skip its edit. last is the hash and time of its last edit, if any.
+pub fn is_unchanged(payload_hash: &str, last: Option<(String, i64)>, now: i64) -> bool {
+    match last {
+        Some((last_hash, edited_at)) => last_hash == payload_hash && now - edited_at < get_max_unchanged_minutes() * 60,
+        None => false
+    }
+}
+
+// How one board's refresh went, times in ms
+#[derive(Debug)]
+#[derive(Default)]
+pub struct BoardRefresh {
+    pub render_ms: i64,
+    pub discord_ms: i64, // editing the board and its extra messages, including waiting on rate limits
+    pub is_ok: bool,
+    pub is_unchanged: bool // the edit was skipped
+}
+
+// Sums up a refresh cycle for the logs
+#[derive(Debug)]
+#[derive(Default)]
+pub struct RefreshReport {
+    pub board_count: usize,
+    pub channel_count: usize,
+    pub skipped_count: usize, // quarantined boards that weren't due for a retry
+    pub failed_count: usize,
+    pub unchanged_count: usize,
+    pub render_count: usize, // boards that render the same are rendered once
+    pub render_ms: i64,
+    pub discord_ms: i64,
+    pub slowest_discord_ms: i64,
+    pub elapsed_ms: i64
+}
+
+impl RefreshReport {
+    pub fn add(&mut self, refresh: &BoardRefresh) {
+        self.board_count += 1;
+        if !refresh.is_ok {
+            self.failed_count += 1;
+        }
+        if refresh.is_unchanged {
+            self.unchanged_count += 1;
+        }
+        self.render_ms += refresh.render_ms;
+        self.discord_ms += refresh.discord_ms;
+        self.slowest_discord_ms = self.slowest_discord_ms.max(refresh.discord_ms);
+    }
+}
+
+impl fmt::Display for RefreshReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Refreshed {} boards in {} channels in {} ms ({} unchanged, {} failed, {} quarantined skipped). {} renders took {} ms and Discord {} ms across boards, the slowest board {} ms.",
+            self.board_count, self.channel_count, self.elapsed_ms, self.unchanged_count, self.failed_count, self.skipped_count, self.render_count, self.render_ms, self.discord_ms, self.slowest_discord_ms)
+    }
+}
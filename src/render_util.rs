@@ -0,0 +1,710 @@
+use crate::Board;
+use crate::xiv_util::{self, PFListing};
+use crate::scraper_util;
+use crate::locale_util::{self, Locale};
+use crate::image_util;
+use crate::schedule_util;
+use crate::template_util::BoardTemplate;
+use poise::serenity_prelude as serenity;
+use serenity::builder::{CreateComponents, CreateEmbed, CreateEmbedFooter};
+use itertools::Itertools;
+
+const MAX_EMBEDS: usize = 10;
+const MAX_FIELDS: usize = 25;
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+#[derive(poise::ChoiceParameter)]
+pub enum BoardLayout {
+    #[name = "Fields (default)"]
+    Fields,
+    #[name = "Compact list"]
+    Compact,
+    #[name = "One embed per listing"]
+    PerListing,
+    #[name = "Table"]
+    Table,
+    #[name = "Image"]
+    Image
+}
+
+impl BoardLayout {
+    pub fn to_db_string(&self) -> Option<&'static str> {
+        match self {
+            BoardLayout::Fields => None,
+            BoardLayout::Compact => Some("compact"),
+            BoardLayout::PerListing => Some("per_listing"),
+            BoardLayout::Table => Some("table"),
+            BoardLayout::Image => Some("image")
+        }
+    }
+
+    pub fn from_db_string(input: &Option<String>) -> BoardLayout {
+        match input.as_deref() {
+            Some("compact") => BoardLayout::Compact,
+            Some("per_listing") => BoardLayout::PerListing,
+            Some("table") => BoardLayout::Table,
+            Some("image") => BoardLayout::Image,
+            _ => BoardLayout::Fields
+        }
+    }
+}
+
+// What a board does with listings that don't fit in one message
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+#[derive(poise::ChoiceParameter)]
+pub enum BoardOverflow {
+    #[name = "Link to xivpf (default)"]
+    Link,
+    #[name = "Extra messages"]
+    Messages,
+    #[name = "Pages with buttons"]
+    Pages
+}
+
+impl BoardOverflow {
+    pub fn to_db_string(&self) -> Option<&'static str> {
+        match self {
+            BoardOverflow::Link => None,
+            BoardOverflow::Messages => Some("messages"),
+            BoardOverflow::Pages => Some("pages")
+        }
+    }
+
+    pub fn from_db_string(input: &Option<String>) -> BoardOverflow {
+        match input.as_deref() {
+            Some("messages") => BoardOverflow::Messages,
+            Some("pages") => BoardOverflow::Pages,
+            _ => BoardOverflow::Link
+        }
+    }
+
+    // How many messages worth of listings get rendered
+    pub fn get_max_pages(&self) -> usize {
+        match self {
+            BoardOverflow::Link => 1,
+            BoardOverflow::Messages => 1 + get_max_overflow_messages(),
+            BoardOverflow::Pages => usize::MAX
+        }
+    }
+}
+
+fn get_max_overflow_messages() -> usize {
+    std::env::var("MAX_OVERFLOW_MESSAGES").unwrap_or("3".to_string()).parse::<usize>().unwrap()
+}
+
+pub const PAGE_PREVIOUS_ID: &str = "xivpf_page_previous";
+pub const PAGE_NEXT_ID: &str = "xivpf_page_next";
+
+// Previous/Next buttons for paged boards, none when everything fits on one page
+pub fn get_page_buttons(page: usize, page_count: usize, locale: Locale) -> CreateComponents {
+    let strings = locale.get_strings();
+    let mut components = CreateComponents::default();
+    if page_count > 1 {
+        components.create_action_row(|row| row
+            .create_button(|b| b.custom_id(PAGE_PREVIOUS_ID).label(strings.previous_page).style(serenity::ButtonStyle::Secondary).disabled(page == 0))
+            .create_button(|b| b.custom_id(PAGE_NEXT_ID).label(strings.next_page).style(serenity::ButtonStyle::Secondary).disabled(page + 1 >= page_count)));
+    }
+    components
+}
+
+// Cuts text down to max_chars characters, ending in an ellipsis if anything was cut
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut result = text.chars().take(max_chars.saturating_sub(1)).collect::<String>();
+    // don't leave a dangling markdown escape behind
+    if result.ends_with('\\') {
+        result.pop();
+    }
+    result.push('…');
+    result
+}
+
+fn get_max_listings() -> usize {
+    std::env::var("MAX_LISTINGS_IN_POST").expect("missing MAX_LISTINGS_IN_POST").parse::<usize>().unwrap()
+}
+
+pub fn get_author(board: &Board, listing: &PFListing) -> String {
+    // boards with several data centers tag each listing
+    if board.data_centers.len() > 1 {
+        format!("{} [{}]", listing.author, listing.data_center)
+    } else {
+        listing.author.to_string()
+    }
+}
+
+pub fn get_flags(board: &Board, listing: &PFListing, show_duty_name: bool) -> String {
+    // category boards span many duties, so each listing says which one it's for
+    let title = if show_duty_name || !board.categories.is_empty() { listing.title.as_str() } else { "" };
+    [title, &listing.flags, &listing.prog.to_display_string()].iter().filter(|x| !x.is_empty()).join(" ")
+}
+
+fn get_role_icons(listing: &PFListing) -> String {
+    listing.slots.iter().map(|x| x.get_emoji_string()).collect::<Vec<String>>().join(" ")
+}
+
+fn get_open_role_icons(listing: &PFListing) -> String {
+    listing.slots.iter().filter(|x| !x.filled).map(|x| x.get_emoji_string()).collect::<Vec<String>>().join("")
+}
+
+fn get_not_shown_text(board: &Board, not_taken: usize) -> String {
+    let strings = board.locale.get_strings();
+    let subjects = board.get_subjects();
+    let count = not_taken.to_string();
+    let text = if subjects.len() == 1 {
+        locale_util::fill(strings.not_shown_duty, &[("count", &count), ("duty", subjects[0])])
+    } else {
+        locale_util::fill(strings.not_shown, &[("count", &count)])
+    };
+    format!("[{}](https://xivpf.com/listings)", text)
+}
+
+fn get_almost_full_open_slots() -> usize {
+    std::env::var("ALMOST_FULL_OPEN_SLOTS").unwrap_or("1".to_string()).parse::<usize>().unwrap()
+}
+
+fn get_expiring_soon_minutes() -> i64 {
+    std::env::var("EXPIRING_SOON_MINUTES").unwrap_or("10".to_string()).parse::<i64>().unwrap()
+}
+
+// Reasons to look at a listing now rather than later
+pub struct ListingMarkers {
+    pub is_new: bool,
+    pub almost_full: bool,
+    pub expiring_soon: bool
+}
+
+// Times are counted from the fetch, so a board shows the same markers until the next one
+pub fn get_markers(listing: &PFListing, times: &RefreshTimes) -> ListingMarkers {
+    let open_slots = listing.slots.len() - listing.get_filled_count();
+    ListingMarkers {
+        is_new: listing.is_new,
+        almost_full: open_slots > 0 && open_slots <= get_almost_full_open_slots(),
+        expiring_soon: listing.expires_at - times.fetched_at <= get_expiring_soon_minutes() * 60
+    }
+}
+
+// The objective badge followed by the markers, e.g. "🟢🆕⌛"
+fn get_badges(listing: &PFListing, times: &RefreshTimes) -> String {
+    let markers = get_markers(listing, times);
+    [listing.get_objective().map(|x| x.get_emoji()), markers.is_new.then(|| "🆕"), markers.almost_full.then(|| "🔥"), markers.expiring_soon.then(|| "⌛")]
+        .iter().flatten().join("")
+}
+
+fn with_badges(listing: &PFListing, times: &RefreshTimes, text: &str) -> String {
+    let badges = get_badges(listing, times);
+    if badges.is_empty() { text.to_string() } else { format!("{} {}", badges, text) }
+}
+
+// The color of the objective most of the board's listings share, otherwise the board's own color
+pub fn get_board_color(board: &Board, listings: &[&PFListing]) -> u32 {
+    // a color picked in the template always wins
+    if board.template.as_ref().and_then(|x| x.color).is_some() {
+        return board.get_color();
+    }
+    [xiv_util::Objective::Practice, xiv_util::Objective::Loot, xiv_util::Objective::Completion].iter()
+        .find(|objective| listings.iter().filter(|x| x.get_objective() == Some(**objective)).count() * 2 > listings.len())
+        .map(|x| x.get_color())
+        .unwrap_or_else(|| board.get_color())
+}
+
+// When the listings were fetched, unix seconds
+#[derive(Clone, Copy)]
+pub struct RefreshTimes {
+    pub fetched_at: i64
+}
+
+// Discord renders these in the reader's time zone and keeps relative ones counting
+fn get_timestamp(unix_time: i64) -> String {
+    format!("<t:{}:R>", unix_time)
+}
+
+// When the board was last updated and how often it's refreshed. There's no next refresh time: boards that didn't change
+// skip their edit, see get_payload_hash, and it would be left in the past.
+pub fn get_refresh_text(board: &Board, locale: Locale, updated: &str) -> String {
+    let minutes = schedule_util::get_refresh_seconds(&board.message_row) / 60;
+    locale_util::fill(locale.get_strings().refresh_times, &[("updated", updated), ("minutes", &minutes.to_string())])
+}
+
+// The lines that close every page: the no listings notice, the not shown link and the refresh times
+fn get_closing_text(board: &Board, times: &RefreshTimes, listing_count: usize, not_taken: usize, show_not_shown: bool) -> String {
+    let strings = board.locale.get_strings();
+    let mut lines = Vec::new();
+    if listing_count == 0 {
+        lines.push(strings.no_listings.to_string());
+    }
+    if not_taken > 0 && show_not_shown {
+        lines.push(get_not_shown_text(board, not_taken));
+    }
+    lines.push(get_refresh_text(board, board.locale, &get_timestamp(times.fetched_at)));
+    lines.join("\n")
+}
+
+// Room kept for the closing text, which is added after listings run out of space. Counts it as if no listing fit,
+// the longest it gets, plus the line break before it.
+fn get_closing_length(board: &Board, times: &RefreshTimes, listing_count: usize, show_not_shown: bool) -> usize {
+    length(&get_closing_text(board, times, listing_count, listing_count, show_not_shown)) + 1
+}
+
+// Values for the placeholders any part of a template can use
+fn get_board_value(board: &Board, name: &str, listing_count: usize) -> String {
+    match name {
+        "title" => board.get_title(),
+        "duties" => board.get_subjects().join(", "),
+        "data_centers" => match xiv_util::get_region_name(&board.data_centers) {
+            Some(region) => region.to_string(),
+            None => board.data_centers.join(", ")
+        },
+        "count" => listing_count.to_string(),
+        _ => "".to_string()
+    }
+}
+
+// Values for the placeholders inside a template's listings loop, None for board placeholders
+fn get_listing_value(board: &Board, listing: &PFListing, times: &RefreshTimes, name: &str) -> Option<String> {
+    Some(match name {
+        "badges" => get_badges(listing, times),
+        "author" => get_author(board, listing),
+        "character" => scraper_util::sanitize(listing.character_name.to_string()),
+        "world" => listing.world.to_string(),
+        "data_center" => listing.data_center.to_string(),
+        "duty" => listing.title.to_string(),
+        "category" => xiv_util::get_category_display_name(&listing.pf_category).to_string(),
+        "roles" => get_role_icons(listing),
+        "open_roles" => get_open_role_icons(listing),
+        "filled" => listing.get_filled_count().to_string(),
+        "slots" => listing.slots.len().to_string(),
+        "flags" => listing.flags.to_string(),
+        "prog" => listing.prog.to_display_string(),
+        "description" => truncate(&listing.description, 500),
+        "updated" => get_timestamp(listing.last_updated_at),
+        "expires" => get_timestamp(listing.expires_at),
+        "min_ilvl" => listing.min_ilvl.trim().to_string(),
+        _ => return None
+    })
+}
+
+// The board's title, from its template if it has one with a title
+fn get_board_title(board: &Board, listing_count: usize) -> String {
+    let title = match &board.template {
+        Some(template) => template.render_title(&|name| get_board_value(board, name, listing_count)),
+        None => "".to_string()
+    };
+    if title.trim().is_empty() { board.get_title() } else { title }
+}
+
+// Discord's embed limits. Going over any of them makes the whole edit fail.
+const MAX_TITLE_LENGTH: usize = 256;
+const MAX_FIELD_NAME_LENGTH: usize = 256;
+const MAX_FIELD_VALUE_LENGTH: usize = 1024;
+const MAX_FOOTER_LENGTH: usize = 2048;
+const MAX_MESSAGE_LENGTH: usize = 6000; // all titles, descriptions, field names and values and footers in a message
+
+fn length(text: &str) -> usize {
+    text.chars().count()
+}
+
+// A CreateEmbed that truncates what goes into it and keeps count of its length
+struct MeasuredEmbed {
+    embed: CreateEmbed,
+    length: usize,
+    field_count: usize
+}
+
+impl MeasuredEmbed {
+    fn new(color: u32) -> MeasuredEmbed {
+        let mut embed = CreateEmbed::default();
+        embed.color(color);
+        MeasuredEmbed { embed, length: 0, field_count: 0 }
+    }
+
+    fn title(&mut self, text: &str) {
+        let text = truncate(text, MAX_TITLE_LENGTH);
+        self.length += length(&text);
+        self.embed.title(text);
+    }
+
+    // Only call once per embed
+    fn description(&mut self, text: &str) {
+        let text = truncate(text, MAX_DESCRIPTION_LENGTH);
+        self.length += length(&text);
+        self.embed.description(text);
+    }
+
+    fn footer(&mut self, text: &str) {
+        let text = truncate(text, MAX_FOOTER_LENGTH);
+        self.length += length(&text);
+        let mut footer = CreateEmbedFooter::default();
+        footer.text(text);
+        self.embed.set_footer(footer);
+    }
+
+    fn field(&mut self, name: &str, value: &str, inline: bool) {
+        let name = truncate(name, MAX_FIELD_NAME_LENGTH);
+        let value = truncate(value, MAX_FIELD_VALUE_LENGTH);
+        self.length += length(&name) + length(&value);
+        self.field_count += 1;
+        self.embed.field(name, value, inline);
+    }
+}
+
+fn get_field_length(name: &str, value: &str) -> usize {
+    std::cmp::min(length(name), MAX_FIELD_NAME_LENGTH) + std::cmp::min(length(value), MAX_FIELD_VALUE_LENGTH)
+}
+
+// title_reserve is room for the page number get_pages adds to the title afterwards
+fn get_header_embed(board: &Board, listing_count: usize, title_reserve: usize) -> MeasuredEmbed {
+    let mut embed = MeasuredEmbed::new(board.get_color());
+    embed.title(&get_board_title(board, listing_count));
+    embed.length += title_reserve;
+    if listing_count == 0 {
+        embed.footer(board.locale.get_strings().no_listings_footer);
+    }
+    embed
+}
+
+// Splits the listings over up to max_pages messages. Only the last page links to listings that still didn't fit.
+pub fn get_pages(board: &Board, mut listings: Vec<&PFListing>, times: &RefreshTimes, max_pages: usize) -> Vec<Vec<CreateEmbed>> {
+    sort_sections(board, &mut listings);
+    // there's one image per message, so image boards don't page
+    let max_pages = if is_image_board(board) { 1 } else { max_pages };
+    // every page takes at least one listing, so there are never more pages than listings
+    let title_reserve = if max_pages > 1 { length(&format!(" ({}/{})", listings.len(), listings.len())) } else { 0 };
+
+    let mut pages = Vec::new();
+    let mut start = 0;
+    loop {
+        let is_last_page = pages.len() + 1 >= max_pages;
+        // a template replaces the layout
+        let (embeds, taken) = match (&board.template, board.layout) {
+            (Some(template), _) => get_template_embed(board, template, &listings[start..], listings.len(), times, is_last_page, title_reserve),
+            (None, BoardLayout::Fields) => get_fields_embed(board, &listings[start..], times, is_last_page, title_reserve),
+            (None, BoardLayout::Compact) => get_compact_embed(board, &listings[start..], times, is_last_page, title_reserve),
+            (None, BoardLayout::PerListing) => get_per_listing_embeds(board, &listings[start..], times, is_last_page, title_reserve),
+            (None, BoardLayout::Table) => get_table_embed(board, &listings[start..], times, is_last_page, title_reserve),
+            (None, BoardLayout::Image) => get_image_embed(board, &listings[start..], times, is_last_page, title_reserve)
+        };
+        start += taken;
+        pages.push(embeds);
+        if is_last_page || taken == 0 || start >= listings.len() {
+            break;
+        }
+    }
+
+    let page_count = pages.len();
+    let color = get_board_color(board, &listings);
+    pages.into_iter().enumerate().map(|(i, mut embeds)| {
+        embeds[0].embed.color(color);
+        if page_count > 1 {
+            embeds[0].embed.title(truncate(&format!("{} ({}/{})", get_board_title(board, listings.len()), i + 1, page_count), MAX_TITLE_LENGTH));
+        }
+        embeds.into_iter().map(|x| x.embed).collect()
+    }).collect()
+}
+
+// Boards with several duties get a section per duty
+fn sort_sections(board: &Board, listings: &mut Vec<&PFListing>) {
+    if board.duty_names.len() > 1 {
+        listings.sort_by_key(|x| board.duty_names.iter().position(|y| y == &x.title));
+    }
+}
+
+fn is_image_board(board: &Board) -> bool {
+    board.template.is_none() && board.layout == BoardLayout::Image
+}
+
+// Listing times in the hash are rounded to this, see get_payload_hash
+const PAYLOAD_TIME_BUCKET_SECONDS: i64 = 10 * 60;
+
+// Identifies what a board shows, so a refresh that would change nothing can skip rendering and the edit.
+// Built from the listings and settings rather than the rendered embeds, which carry the fetch time and listing times
+// that move with every fetch. Listing times are rounded: Discord keeps relative timestamps counting between edits,
+// and refresh_util::get_max_unchanged_minutes bounds how stale the times drawn on image boards get.
+pub fn get_payload_hash(board: &Board, listings: &[&PFListing], page: usize, times: &RefreshTimes) -> String {
+    let bucket = |time: i64| time.div_euclid(PAYLOAD_TIME_BUCKET_SECONDS);
+    let mut payload = format!("{}\n{}\n{:?}\n{}\n", board.get_render_key(), page, board.template, listings.len());
+    for listing in listings {
+        let markers = get_markers(listing, times);
+        payload += &format!("{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}\n", listing.id, listing.title, listing.pf_category, listing.data_center, listing.author,
+            listing.flags, listing.description, listing.min_ilvl, listing.prog.to_display_string(), listing.slots.iter().map(|x| x.to_string()).join(","),
+            markers.is_new, markers.almost_full, markers.expiring_soon, bucket(listing.last_updated_at), bucket(listing.expires_at));
+    }
+    format!("{:016x}", xiv_util::get_stable_hash(payload.as_bytes()))
+}
+
+// The PNG an image board attaches, None for every other layout
+pub fn get_board_image(board: &Board, mut listings: Vec<&PFListing>, times: &RefreshTimes) -> Option<Vec<u8>> {
+    if !is_image_board(board) {
+        return None;
+    }
+    sort_sections(board, &mut listings);
+    match image_util::render_board(board, &listings, times, image_util::FALLBACK_FONT.as_ref()) {
+        Ok(image) => Some(image),
+        Err(e) => {
+            println!("Error rendering image for {}: {}", board.message_row.message_id, e);
+            None
+        }
+    }
+}
+
+// Three inline fields per listing: author and roles, flags and description, times
+fn get_fields_embed(board: &Board, listings: &[&PFListing], times: &RefreshTimes, show_not_shown: bool, title_reserve: usize) -> (Vec<MeasuredEmbed>, usize) {
+    let mut embed = get_header_embed(board, listings.len(), title_reserve);
+    let show_duty_sections = board.duty_names.len() > 1;
+
+    let closing_length = get_closing_length(board, times, listings.len(), show_not_shown);
+    let mut taken = 0;
+    let mut current_duty: Option<&str> = None;
+    for listing in listings.iter().take(get_max_listings()) {
+        let mut fields: Vec<(String, String, bool)> = Vec::new();
+        let new_section = show_duty_sections && current_duty != Some(listing.title.as_str());
+        if new_section {
+            fields.push((format!("__{}__", listing.title), "\u{200b}".to_string(), false));
+        }
+        fields.push((with_badges(listing, times, &get_author(board, listing)), get_role_icons(listing), true));
+        let flags = get_flags(board, listing, false);
+        fields.push((if flags.is_empty() { "\u{200b}".to_string() } else { flags }, listing.description.to_string(), true));
+        fields.push(("\u{200b}".to_string(), format!("<:ffxivstopwatch:987141580869730324> {}\n<:ffxivhourglass:987141579879878676> {}",
+            get_timestamp(listing.last_updated_at), get_timestamp(listing.expires_at)), true));
+
+        // one field and its length are kept for the closing text
+        let fields_length = fields.iter().map(|(name, value, _)| get_field_length(name, value)).sum::<usize>();
+        if embed.field_count + fields.len() > MAX_FIELDS - 1 || embed.length + fields_length + closing_length > MAX_MESSAGE_LENGTH {
+            break;
+        }
+        for (name, value, inline) in fields {
+            embed.field(&name, &value, inline);
+        }
+        if new_section {
+            current_duty = Some(listing.title.as_str());
+        }
+        taken += 1;
+    }
+
+    embed.field("\u{200b}", &get_closing_text(board, times, listings.len(), listings.len() - taken, show_not_shown), false);
+    (vec![embed], taken)
+}
+
+// Adds lines to the embed description until it's full, returns how many listings made it in
+fn fill_description(embed: &mut MeasuredEmbed, board: &Board, listings: &[&PFListing], times: &RefreshTimes, show_not_shown: bool, header: &str, footer: &str,
+    get_section: impl Fn(&str) -> String, get_line: impl Fn(&PFListing) -> String) -> usize {
+    let show_duty_sections = board.duty_names.len() > 1;
+    let max_length = std::cmp::min(MAX_DESCRIPTION_LENGTH, MAX_MESSAGE_LENGTH.saturating_sub(embed.length))
+        .saturating_sub(get_closing_length(board, times, listings.len(), show_not_shown) + length(header) + length(footer));
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut lines_length = 0;
+    let mut taken = 0;
+    let mut current_duty: Option<&str> = None;
+    for listing in listings.iter().take(get_max_listings()) {
+        let mut new_lines = Vec::new();
+        if show_duty_sections && current_duty != Some(listing.title.as_str()) {
+            new_lines.push(get_section(&listing.title));
+        }
+        new_lines.push(get_line(listing));
+        let new_length = new_lines.iter().map(|x| length(x) + 1).sum::<usize>();
+        if lines_length + new_length > max_length {
+            break;
+        }
+        current_duty = Some(listing.title.as_str());
+        lines_length += new_length;
+        lines.append(&mut new_lines);
+        taken += 1;
+    }
+
+    let description = if lines.is_empty() { "".to_string() } else { format!("{}{}{}", header, lines.join("\n"), footer) };
+    let closing = get_closing_text(board, times, listings.len(), listings.len() - taken, show_not_shown);
+    embed.description(format!("{}\n{}", description, closing).trim_start());
+    taken
+}
+
+// One line per listing: open roles, author, flags, description and last update
+fn get_compact_embed(board: &Board, listings: &[&PFListing], times: &RefreshTimes, show_not_shown: bool, title_reserve: usize) -> (Vec<MeasuredEmbed>, usize) {
+    let mut embed = get_header_embed(board, listings.len(), title_reserve);
+    let taken = fill_description(&mut embed, board, listings, times, show_not_shown, "", "",
+        |duty_name| format!("__**{}**__", duty_name),
+        |listing| {
+            let flags = get_flags(board, listing, false);
+            format!("{} {} ({}/{}) {}{} · {}", get_open_role_icons(listing), with_badges(listing, times, &format!("**{}**", get_author(board, listing))), listing.get_filled_count(), listing.slots.len(),
+                if flags.is_empty() { "".to_string() } else { format!("{} ", flags) }, truncate(&listing.description.replace('\n', " "), 100), get_timestamp(listing.last_updated_at))
+        });
+    (vec![embed], taken)
+}
+
+// A monospace table, which lines up on any screen width that fits it. Badges are left out since emoji don't keep to the columns.
+fn get_table_embed(board: &Board, listings: &[&PFListing], times: &RefreshTimes, show_not_shown: bool, title_reserve: usize) -> (Vec<MeasuredEmbed>, usize) {
+    let mut embed = get_header_embed(board, listings.len(), title_reserve);
+    let plain = |text: &str, max_chars: usize| truncate(&scraper_util::unsanitize(text).replace('`', "'").replace('\n', " "), max_chars);
+    let strings = board.locale.get_strings();
+    let header = format!("```\n{:<20} {:>5} {:>4} {}\n", strings.table_author, strings.table_party, strings.table_updated, strings.table_description);
+    let taken = fill_description(&mut embed, board, listings, times, show_not_shown, &header, "\n```",
+        |duty_name| format!("-- {} --", plain(duty_name, 40)),
+        |listing| {
+            let author = if board.data_centers.len() > 1 { format!("{} [{}]", listing.character_name, listing.data_center) } else { listing.character_name.to_string() };
+            format!("{:<20} {:>5} {:>4} {}", plain(&author, 20), format!("{}/{}", listing.get_filled_count(), listing.slots.len()),
+                format!("{}m", xiv_util::parse_relative_minutes(&listing.last_updated)), plain(&listing.description, 40))
+        });
+    (vec![embed], taken)
+}
+
+// A header embed followed by one embed per listing
+fn get_per_listing_embeds(board: &Board, listings: &[&PFListing], times: &RefreshTimes, show_not_shown: bool, title_reserve: usize) -> (Vec<MeasuredEmbed>, usize) {
+    let mut embeds = vec![get_header_embed(board, listings.len(), title_reserve)];
+    let show_duty_name = board.duty_names.len() > 1;
+    let mut message_length = embeds[0].length;
+
+    let max_to_take = std::cmp::min(get_max_listings(), MAX_EMBEDS - 1);
+    let closing_length = get_closing_length(board, times, listings.len(), show_not_shown);
+    for listing in listings.iter().take(max_to_take) {
+        let mut embed = MeasuredEmbed::new(listing.get_objective().map(|x| x.get_color()).unwrap_or_else(|| board.get_color()));
+        embed.title(&with_badges(listing, times, &get_author(board, listing)));
+        let flags = get_flags(board, listing, show_duty_name);
+        let flags = if flags.is_empty() { "".to_string() } else { format!("**{}**\n", flags) };
+        // footers don't render timestamps, so the times go in the description
+        let listing_times = locale_util::fill(board.locale.get_strings().listing_times,
+            &[("updated", &get_timestamp(listing.last_updated_at)), ("expires", &get_timestamp(listing.expires_at))]);
+        embed.description(&format!("{}\n{}{}\n{}", get_role_icons(listing), flags, truncate(&listing.description, 500), listing_times));
+        if message_length + embed.length + closing_length > MAX_MESSAGE_LENGTH {
+            break;
+        }
+        message_length += embed.length;
+        embeds.push(embed);
+    }
+
+    let taken = embeds.len() - 1;
+    embeds[0].description(&get_closing_text(board, times, listings.len(), listings.len() - taken, show_not_shown));
+    (embeds, taken)
+}
+
+// The header embed showing the attached board image
+fn get_image_embed(board: &Board, listings: &[&PFListing], times: &RefreshTimes, show_not_shown: bool, title_reserve: usize) -> (Vec<MeasuredEmbed>, usize) {
+    let mut embed = get_header_embed(board, listings.len(), title_reserve);
+    let taken = std::cmp::min(listings.len(), image_util::MAX_LISTINGS);
+    embed.description(&get_closing_text(board, times, listings.len(), listings.len() - taken, show_not_shown));
+    embed.embed.attachment(image_util::FILE_NAME);
+    (vec![embed], taken)
+}
+
+// The text around the template's listings loop, with the loop repeated for as many listings as fit
+fn get_template_embed(board: &Board, template: &BoardTemplate, listings: &[&PFListing], listing_count: usize, times: &RefreshTimes, show_not_shown: bool, title_reserve: usize) -> (Vec<MeasuredEmbed>, usize) {
+    let mut embed = MeasuredEmbed::new(board.get_color());
+    embed.title(&get_board_title(board, listing_count));
+    embed.length += title_reserve;
+    let board_value = |name: &str| get_board_value(board, name, listing_count);
+    let footer = template.render_footer(&board_value);
+    if !footer.trim().is_empty() {
+        embed.footer(&footer);
+    } else if listings.is_empty() {
+        embed.footer(board.locale.get_strings().no_listings_footer);
+    }
+
+    let before = template.render_before_listings(&board_value);
+    let after = template.render_after_listings(&board_value);
+    let max_length = std::cmp::min(MAX_DESCRIPTION_LENGTH, MAX_MESSAGE_LENGTH.saturating_sub(embed.length))
+        .saturating_sub(get_closing_length(board, times, listings.len(), show_not_shown) + length(&before) + length(&after));
+
+    let mut body = String::new();
+    let mut taken = 0;
+    for listing in listings.iter().take(get_max_listings()) {
+        let text = template.render_listing(&|name| get_listing_value(board, listing, times, name).unwrap_or_else(|| board_value(name)));
+        if length(&body) + length(&text) > max_length {
+            break;
+        }
+        body.push_str(&text);
+        taken += 1;
+    }
+
+    let closing = get_closing_text(board, times, listings.len(), listings.len() - taken, show_not_shown);
+    embed.description(format!("{}{}{}\n{}", before, body, after, closing).trim_start());
+    (vec![embed], taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_util::tests::{get_board, get_example_listings, FETCHED_AT};
+
+    const TIMES: RefreshTimes = RefreshTimes { fetched_at: FETCHED_AT };
+
+    fn get_hash(board: &Board, listings: &[PFListing], times: &RefreshTimes) -> String {
+        get_payload_hash(board, &listings.iter().collect::<Vec<_>>(), 0, times)
+    }
+
+    #[test]
+    fn payload_hash_ignores_what_moves_every_fetch() {
+        let board = get_board("Dragonsong's Reprise (Ultimate)", "Primal", Locale::En);
+        // FETCHED_AT starts a bucket, so these times can move 5 minutes without leaving theirs
+        let mut listings = get_example_listings(&board);
+        for listing in listings.iter_mut() {
+            listing.last_updated_at = FETCHED_AT - 1200;
+            listing.expires_at = FETCHED_AT + 3600;
+        }
+        let mut next_fetch = listings.clone();
+        for listing in next_fetch.iter_mut() {
+            listing.last_updated_at += 300;
+            listing.expires_at += 300;
+        }
+        let times = RefreshTimes { fetched_at: FETCHED_AT + 300 };
+        let markers = |listings: &[PFListing], times: &RefreshTimes| listings.iter().map(|x| get_markers(x, times)).map(|x| (x.is_new, x.almost_full, x.expiring_soon)).collect::<Vec<_>>();
+        assert_eq!(markers(&listings, &TIMES), markers(&next_fetch, &times));
+        assert_eq!(get_hash(&board, &listings, &TIMES), get_hash(&board, &next_fetch, &times));
+    }
+
+    // Every Discord timestamp on the pages
+    fn get_timestamps(pages: &[Vec<CreateEmbed>]) -> Vec<i64> {
+        let text = serde_json::to_string(&pages.iter().flatten().map(|x| &x.0).collect::<Vec<_>>()).unwrap();
+        regex::Regex::new(r"<t:(\d+):").unwrap().captures_iter(&text).map(|x| x[1].parse::<i64>().unwrap()).collect()
+    }
+
+    #[test]
+    fn skipped_boards_never_show_a_past_refresh_time() {
+        let board = get_board("Dragonsong's Reprise (Ultimate)", "Primal", Locale::En);
+        let mut listings = get_example_listings(&board);
+        for listing in listings.iter_mut() {
+            listing.expires_at = FETCHED_AT + 3600;
+        }
+        // two refreshes later nothing changed, so the board keeps what it showed at the first
+        let later = RefreshTimes { fetched_at: FETCHED_AT + schedule_util::get_refresh_seconds(&board.message_row) * 2 };
+        assert_eq!(get_hash(&board, &listings, &TIMES), get_hash(&board, &listings, &later));
+
+        let listing_times = listings.iter().flat_map(|x| [x.last_updated_at, x.expires_at]).collect::<Vec<i64>>();
+        let timestamps = get_timestamps(&get_pages(&board, listings.iter().collect(), &TIMES, 1));
+        assert!(timestamps.contains(&TIMES.fetched_at));
+        for timestamp in timestamps {
+            assert!(timestamp == TIMES.fetched_at || listing_times.contains(&timestamp), "{} isn't the fetch or a listing time", timestamp);
+        }
+    }
+
+    #[test]
+    fn payload_hash_rounds_listing_times() {
+        let board = get_board("Dragonsong's Reprise (Ultimate)", "Primal", Locale::En);
+        let mut listings = get_example_listings(&board);
+        listings[0].last_updated_at = FETCHED_AT - 600;
+        let hash = get_hash(&board, &listings, &TIMES);
+        listings[0].last_updated_at = FETCHED_AT - 540;
+        assert_eq!(get_hash(&board, &listings, &TIMES), hash);
+        listings[0].last_updated_at = FETCHED_AT;
+        assert_ne!(get_hash(&board, &listings, &TIMES), hash);
+    }
+
+    #[test]
+    fn payload_hash_changes_with_the_listings() {
+        let board = get_board("Dragonsong's Reprise (Ultimate)", "Primal", Locale::En);
+        let listings = get_example_listings(&board);
+        let hash = get_hash(&board, &listings, &TIMES);
+
+        let mut filled = listings.clone();
+        let slot = filled[0].slots.iter_mut().find(|x| !x.filled).unwrap();
+        slot.filled = true;
+        assert_ne!(get_hash(&board, &filled, &TIMES), hash);
+        assert_ne!(get_hash(&board, &listings[1..], &TIMES), hash);
+        assert_ne!(get_payload_hash(&board, &listings.iter().collect::<Vec<_>>(), 1, &TIMES), hash);
+        assert_ne!(get_hash(&get_board("Dragonsong's Reprise (Ultimate)", "Primal", Locale::De), &listings, &TIMES), hash);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::storage_util::MessageRow;
+use crate::xiv_util;
+
+pub const MIN_REFRESH_MINUTES: i64 = 5;
+pub const MAX_REFRESH_MINUTES: i64 = 60;
+
+// How often xivpf is fetched, apart from the boards
+pub fn get_scrape_interval_seconds() -> i64 {
+    std::env::var("SCRAPE_INTERVAL_SECONDS").unwrap_or("300".to_string()).parse::<i64>().unwrap()
+}
+
+// How often the scheduler looks for boards that are due
+pub fn get_tick_seconds() -> u64 {
+    std::env::var("BOARD_SCHEDULER_TICK_SECONDS").unwrap_or("20".to_string()).parse::<u64>().unwrap().max(1)
+}
+
+// For boards without a refresh interval of their own
+fn get_default_refresh_minutes() -> i64 {
+    std::env::var("BOARD_REFRESH_MINUTES").unwrap_or("5".to_string()).parse::<i64>().unwrap()
+}
+
+pub fn get_refresh_seconds(message_row: &MessageRow) -> i64 {
+    message_row.refresh_minutes.unwrap_or_else(get_default_refresh_minutes).clamp(MIN_REFRESH_MINUTES, MAX_REFRESH_MINUTES) * 60
+}
+
+// Where in its interval a board refreshes, taken from its message id so boards spread out evenly and keep their spot
+fn get_offset(message_id: &str, refresh_seconds: i64) -> i64 {
+    (xiv_util::get_stable_hash(message_id.as_bytes()) % refresh_seconds as u64) as i64
+}
+
+// The board's next spot after now
+pub fn get_next_refresh_at(message_row: &MessageRow, now: i64) -> i64 {
+    let refresh_seconds = get_refresh_seconds(message_row);
+    let offset = get_offset(&message_row.message_id, refresh_seconds);
+    now - (now - offset).rem_euclid(refresh_seconds) + refresh_seconds
+}
+
+// Boards never scheduled yet go now, the rest when their time comes
+pub fn is_due(message_row: &MessageRow, now: i64) -> bool {
+    message_row.next_refresh_at.map(|x| x <= now).unwrap_or(true)
+}
+
+// For the first pass after a restart. Boards that came due while the bot was down, or that were never scheduled,
+// get spread over their interval instead of all going at once.
+pub fn is_overdue(message_row: &MessageRow, now: i64) -> bool {
+    message_row.next_refresh_at.map(|x| now - x > get_tick_seconds() as i64 * 2).unwrap_or(true)
+}
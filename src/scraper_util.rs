@@ -1,4 +1,5 @@
 use crate::xiv_util;
+use crate::prog_util;
 use std::str::FromStr;
 use std::fs;
 use scraper::{Html, Selector};
@@ -9,7 +10,24 @@ pub fn sanitize(input: String) -> String {
         .replace("[", "\\[").replace("*", "\\*").replace("_", "\\_").replace("`", "\\`").replace(">", "\\>")
 }
 
-pub fn get_listings<'a>(html: String) -> Vec<xiv_util::PFListing> {
+// Undoes sanitize, for matching or showing the text as it was written
+pub fn unsanitize(input: &str) -> String {
+    let mut result = String::new();
+    let mut chars = input.chars().filter(|c| *c != '\u{200B}');
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                result.push(escaped);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+// fetched_at is when the html was downloaded, which xivpf's relative times count from
+pub fn get_listings<'a>(html: String, fetched_at: i64) -> Vec<xiv_util::PFListing> {
     let document = Html::parse_document(&html);
     let listing_selector = Selector::parse(".listing").unwrap();
 
@@ -40,18 +58,31 @@ pub fn get_listings<'a>(html: String) -> Vec<xiv_util::PFListing> {
         let min_ilvl = element.select(&Selector::parse(".middle .stat .value").unwrap()).next().ok_or(SimpleError::new("parse error"))?.text().last().ok_or(SimpleError::new("parse error"))?.to_owned();
         let data_center = element.value().attr("data-centre").ok_or(SimpleError::new("parse error"))?.to_string();
         let pf_category = element.value().attr("data-pf-category").ok_or(SimpleError::new("parse error"))?.to_string();
+        let (character_name, world) = match author.split_once(" @ ") {
+            Some((x, y)) => (x.to_string(), y.to_string()),
+            None => (author.to_string(), "".to_string())
+        };
+        let prog = prog_util::get_prog_tag(&title, &description);
+        let id = element.value().attr("data-id").ok_or(SimpleError::new("parse error"))?.parse::<u64>().map_err(|_| SimpleError::new("parse error"))?;
 
         Result::<xiv_util::PFListing, SimpleError>::Ok(xiv_util::PFListing {
+            id,
             title, 
             author: sanitize(author),
+            character_name,
+            world,
             flags,
             description: sanitize(description),
             slots,
+            last_updated_at: fetched_at - xiv_util::parse_relative_minutes(&last_updated) as i64 * 60,
+            expires_at: fetched_at + xiv_util::parse_relative_minutes(&expires_in) as i64 * 60,
             expires_in,
             last_updated,
             min_ilvl,
             data_center,
-            pf_category
+            pf_category,
+            prog,
+            is_new: false
         })
     }).filter_map(|w: Result<xiv_util::PFListing, SimpleError>| w.ok()).collect::<Vec<_>>();
     listings.sort_by(|a, b| b.flags.len().partial_cmp(&a.flags.len()).unwrap());
@@ -71,5 +102,5 @@ pub fn get_listings<'a>(html: String) -> Vec<xiv_util::PFListing> {
 
 pub async fn get_sample_listings() -> Vec<xiv_util::PFListing> {
     let html = fs::read_to_string("scrape_example.html").expect("Unable to read");
-    get_listings(html)
+    get_listings(html, xiv_util::get_unix_time())
 }
\ No newline at end of file
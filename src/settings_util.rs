@@ -0,0 +1,113 @@
+use crate::locale_util::Locale;
+use crate::render_util::BoardLayout;
+
+// Per guild defaults. Everything is optional, unset falls back to what boards did before there were settings.
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Clone)]
+#[derive(sqlx::FromRow)]
+pub struct GuildSettings {
+    pub data_center: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>, // fixed UTC offset as saved by format_timezone, e.g. "UTC+09:00". Zones with daylight saving aren't supported.
+    pub manager_role_id: Option<String>,
+    pub layout: Option<String>,
+    pub allow_statics: Option<i64>,
+    pub include_filter: Option<String>,
+    pub exclude_filter: Option<String>,
+    pub log_channel_id: Option<String> // where the bot says when a board stops or starts updating again
+}
+
+impl GuildSettings {
+    pub fn get_locale(&self) -> Locale {
+        Locale::from_db_string(&self.locale)
+    }
+
+    pub fn get_layout(&self) -> BoardLayout {
+        BoardLayout::from_db_string(&self.layout)
+    }
+
+    // Minutes ahead of UTC
+    pub fn get_utc_offset(&self) -> i32 {
+        self.timezone.as_deref().and_then(|x| parse_timezone(x).ok()).unwrap_or(0)
+    }
+
+    pub fn reset(&mut self, setting: Setting) {
+        match setting {
+            Setting::DataCenter => self.data_center = None,
+            Setting::Language => self.locale = None,
+            Setting::Timezone => self.timezone = None,
+            Setting::ManagerRole => self.manager_role_id = None,
+            Setting::Layout => self.layout = None,
+            Setting::AllowStatics => self.allow_statics = None,
+            Setting::IncludeFilter => self.include_filter = None,
+            Setting::ExcludeFilter => self.exclude_filter = None,
+            Setting::LogChannel => self.log_channel_id = None,
+            Setting::All => *self = GuildSettings::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+#[derive(poise::ChoiceParameter)]
+pub enum Setting {
+    #[name = "Data center"]
+    DataCenter,
+    #[name = "Language"]
+    Language,
+    #[name = "UTC offset"]
+    Timezone,
+    #[name = "Manager role"]
+    ManagerRole,
+    #[name = "Layout"]
+    Layout,
+    #[name = "Allow statics"]
+    AllowStatics,
+    #[name = "Include filter"]
+    IncludeFilter,
+    #[name = "Exclude filter"]
+    ExcludeFilter,
+    #[name = "Log channel"]
+    LogChannel,
+    #[name = "Everything"]
+    All
+}
+
+// Takes an offset like "+9", "-05:30", "UTC+9" or "GMT-3", returns minutes ahead of UTC.
+// The bot has no timezone database, so the offset stays the same all year.
+pub fn parse_timezone(input: &str) -> Result<i32, String> {
+    if input.contains('/') {
+        return Err(format!("Zone names like `{}` aren't supported, only a fixed UTC offset, e.g. UTC+1 or -05:00. \
+            It doesn't change for daylight saving time.", input.replace('`', "'")));
+    }
+    let error = || format!("Invalid UTC offset `{}`. Give one like UTC+9 or -05:30.", input.replace('`', "'"));
+    let text = input.trim().to_uppercase();
+    let text = text.trim_start_matches("UTC").trim_start_matches("GMT").trim();
+    if text.is_empty() {
+        return Ok(0);
+    }
+    let (sign, text) = match text.chars().next() {
+        Some('+') => (1, &text[1..]),
+        Some('-') => (-1, &text[1..]),
+        _ => return Err(error())
+    };
+    let (hours, minutes) = text.split_once(':').unwrap_or((text, "0"));
+    let hours = hours.parse::<i32>().map_err(|_| error())?;
+    let minutes = minutes.parse::<i32>().map_err(|_| error())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(error());
+    }
+    Ok(sign * (hours * 60 + minutes))
+}
+
+pub fn format_timezone(offset: i32) -> String {
+    format!("UTC{}{:02}:{:02}", if offset < 0 { "-" } else { "+" }, offset.abs() / 60, offset.abs() % 60)
+}
+
+// e.g. "19:05"
+pub fn format_time(unix_time: i64, utc_offset: i32) -> String {
+    let seconds = (unix_time + utc_offset as i64 * 60).rem_euclid(24 * 60 * 60);
+    format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+}
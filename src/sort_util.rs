@@ -0,0 +1,85 @@
+use crate::xiv_util::{PFListing, Role};
+use std::cmp::Ordering;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+#[derive(poise::ChoiceParameter)]
+pub enum SortMode {
+    #[name = "xivpf order"]
+    Default,
+    #[name = "Most recently updated"]
+    RecentlyUpdated,
+    #[name = "Soonest expiring"]
+    SoonestExpiring,
+    #[name = "Most filled"]
+    MostFilled,
+    #[name = "Fewest open tank slots"]
+    FewestTankSlots,
+    #[name = "Fewest open healer slots"]
+    FewestHealerSlots,
+    #[name = "Fewest open dps slots"]
+    FewestDpsSlots,
+    #[name = "Highest min ilvl"]
+    MinIlvl,
+    #[name = "Furthest prog point"]
+    FurthestProg
+}
+
+impl SortMode {
+    pub fn to_db_string(&self) -> Option<&'static str> {
+        match self {
+            SortMode::Default => None,
+            SortMode::RecentlyUpdated => Some("updated"),
+            SortMode::SoonestExpiring => Some("expiring"),
+            SortMode::MostFilled => Some("filled"),
+            SortMode::FewestTankSlots => Some("role_tank"),
+            SortMode::FewestHealerSlots => Some("role_healer"),
+            SortMode::FewestDpsSlots => Some("role_dps"),
+            SortMode::MinIlvl => Some("ilvl"),
+            SortMode::FurthestProg => Some("prog")
+        }
+    }
+
+    pub fn from_db_string(input: &Option<String>) -> SortMode {
+        match input.as_deref() {
+            Some("updated") => SortMode::RecentlyUpdated,
+            Some("expiring") => SortMode::SoonestExpiring,
+            Some("filled") => SortMode::MostFilled,
+            Some("role_tank") => SortMode::FewestTankSlots,
+            Some("role_healer") => SortMode::FewestHealerSlots,
+            Some("role_dps") => SortMode::FewestDpsSlots,
+            Some("ilvl") => SortMode::MinIlvl,
+            Some("prog") => SortMode::FurthestProg,
+            _ => SortMode::Default
+        }
+    }
+}
+
+fn compare_open_for_role(a: &PFListing, b: &PFListing, role: Role) -> Ordering {
+    // listings with no room for the role go last
+    let a_open = a.get_open_count_for_role(role);
+    let b_open = b.get_open_count_for_role(role);
+    (a_open == 0).cmp(&(b_open == 0)).then(a_open.cmp(&b_open))
+}
+
+fn compare(a: &PFListing, b: &PFListing, sort_mode: SortMode) -> Ordering {
+    match sort_mode {
+        // same order get_listings uses, listings with flags first
+        SortMode::Default => b.flags.len().cmp(&a.flags.len()),
+        SortMode::RecentlyUpdated => b.last_updated_at.cmp(&a.last_updated_at),
+        SortMode::SoonestExpiring => a.expires_at.cmp(&b.expires_at),
+        // by fraction of the party filled, so 4 man and 8 man listings compare fairly
+        SortMode::MostFilled => (b.get_filled_count() * a.slots.len()).cmp(&(a.get_filled_count() * b.slots.len())),
+        SortMode::FewestTankSlots => compare_open_for_role(a, b, Role::Tank),
+        SortMode::FewestHealerSlots => compare_open_for_role(a, b, Role::Healer),
+        SortMode::FewestDpsSlots => compare_open_for_role(a, b, Role::DPS),
+        SortMode::MinIlvl => b.get_min_ilvl().cmp(&a.get_min_ilvl()),
+        SortMode::FurthestProg => b.prog.get_phase().cmp(&a.prog.get_phase())
+    }
+}
+
+// Ties are broken by listing id, so rows don't shuffle between refreshes
+pub fn sort_listings(listings: &mut Vec<&PFListing>, sort_mode: SortMode) {
+    listings.sort_by(|a, b| compare(a, b, sort_mode).then(a.id.cmp(&b.id)));
+}
@@ -0,0 +1,466 @@
+use crate::history_util;
+use crate::settings_util::GuildSettings;
+use crate::storage_util::{Error, MessageLists, MessageRow, Storage};
+use crate::template_util::TemplateSource;
+use crate::xiv_util::PFListing;
+use poise::serenity_prelude as serenity;
+use sqlx::migrate::Migrate;
+use std::collections::HashMap;
+
+pub struct SqliteStorage {
+    pool: sqlx::SqlitePool
+}
+
+impl SqliteStorage {
+    pub async fn connect(filename: &str) -> Result<SqliteStorage, Error> {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(
+                sqlx::sqlite::SqliteConnectOptions::new()
+                    .filename(filename)
+                    .create_if_missing(true),
+            )
+            .await?;
+        Ok(SqliteStorage { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for SqliteStorage {
+    // Backs the database up first if there's anything to apply
+    async fn migrate(&self) -> Result<(), Error> {
+        let migrator = sqlx::migrate!("./migrations");
+        let applied = {
+            let mut connection = self.pool.acquire().await?;
+            connection.ensure_migrations_table().await?;
+            connection.list_applied_migrations().await?
+        };
+        let pending = crate::storage_util::get_pending_migrations(&migrator, &applied)?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        // a new database has nothing to lose
+        if !applied.is_empty() {
+            let backup_path = format!("database.sqlite.{}.bak", serenity::Timestamp::now().to_string().chars().take(19).collect::<String>().replace(':', "-"));
+            sqlx::query("VACUUM INTO ?")
+                .bind(&backup_path)
+                .execute(&self.pool)
+                .await?;
+            println!("Backed up the database to {}", backup_path);
+        }
+        migrator.run(&self.pool).await?;
+        println!("Applied {} database migrations: {}", pending.len(), pending.join(", "));
+        Ok(())
+    }
+
+    async fn add_guild(&self, guild_id: &str, guild_name: &str) -> Result<(), Error> {
+        sqlx::query!("INSERT OR IGNORE INTO guilds(guild_id, guild_name) VALUES(?, ?)", guild_id, guild_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_guild_name(&self, guild_id: &str, guild_name: &str) -> Result<(), Error> {
+        sqlx::query!("UPDATE guilds SET guild_name=? WHERE guild_id=?", guild_name, guild_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_guild(&self, guild_id: &str) -> Result<Vec<String>, Error> {
+        let mut transaction = self.pool.begin().await?;
+        let message_ids = sqlx::query!("SELECT message_id FROM messages WHERE guild_id=?", guild_id)
+            .fetch_all(&mut transaction)
+            .await?
+            .into_iter().map(|x| x.message_id).collect();
+        // the rest of each board goes with its messages row, and settings with the guilds row
+        sqlx::query!("DELETE FROM messages WHERE guild_id=?", guild_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("DELETE FROM guild_blocked_authors WHERE guild_id=?", guild_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("DELETE FROM guild_blocked_keywords WHERE guild_id=?", guild_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("DELETE FROM guilds WHERE guild_id=?", guild_id)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(message_ids)
+    }
+
+    async fn get_guild_settings(&self, guild_id: &str) -> Result<Option<GuildSettings>, Error> {
+        Ok(sqlx::query_as!(GuildSettings, "SELECT data_center, locale, timezone, manager_role_id, layout, allow_statics, include_filter, exclude_filter, log_channel_id FROM guild_settings WHERE guild_id=?", guild_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn get_all_guild_settings(&self) -> Result<HashMap<String, GuildSettings>, Error> {
+        Ok(sqlx::query!("SELECT guild_id, data_center, locale, timezone, manager_role_id, layout, allow_statics, include_filter, exclude_filter, log_channel_id FROM guild_settings")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| (x.guild_id, GuildSettings { data_center: x.data_center, locale: x.locale, timezone: x.timezone, manager_role_id: x.manager_role_id,
+                layout: x.layout, allow_statics: x.allow_statics, include_filter: x.include_filter, exclude_filter: x.exclude_filter, log_channel_id: x.log_channel_id })).collect())
+    }
+
+    async fn save_guild_settings(&self, guild_id: &str, settings: &GuildSettings) -> Result<(), Error> {
+        sqlx::query!("INSERT OR REPLACE INTO guild_settings(guild_id, data_center, locale, timezone, manager_role_id, layout, allow_statics, include_filter, exclude_filter, log_channel_id) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            guild_id, settings.data_center, settings.locale, settings.timezone, settings.manager_role_id, settings.layout, settings.allow_statics, settings.include_filter, settings.exclude_filter, settings.log_channel_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_blocked_authors(&self, guild_id: &str) -> Result<Vec<(String, String)>, Error> {
+        Ok(sqlx::query!("SELECT character_name, world FROM guild_blocked_authors WHERE guild_id=? ORDER BY character_name", guild_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| (x.character_name, x.world)).collect())
+    }
+
+    async fn add_blocked_author(&self, guild_id: &str, character_name: &str, world: &str) -> Result<(), Error> {
+        sqlx::query!("INSERT OR IGNORE INTO guild_blocked_authors(guild_id, character_name, world) VALUES(?, ?, ?)", guild_id, character_name, world)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_blocked_author(&self, guild_id: &str, character_name: &str, world: &str) -> Result<bool, Error> {
+        let result = sqlx::query!("DELETE FROM guild_blocked_authors WHERE guild_id=? AND character_name=? COLLATE NOCASE AND world=? COLLATE NOCASE", guild_id, character_name, world)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_blocked_keywords(&self, guild_id: &str) -> Result<Vec<(String, bool)>, Error> {
+        Ok(sqlx::query!("SELECT keyword, is_regex FROM guild_blocked_keywords WHERE guild_id=? ORDER BY keyword", guild_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| (x.keyword, x.is_regex == 1)).collect())
+    }
+
+    async fn add_blocked_keyword(&self, guild_id: &str, keyword: &str, is_regex: bool) -> Result<(), Error> {
+        let is_regex_i = if is_regex {1} else {0};
+        sqlx::query!("INSERT OR IGNORE INTO guild_blocked_keywords(guild_id, keyword, is_regex) VALUES(?, ?, ?)", guild_id, keyword, is_regex_i)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_blocked_keyword(&self, guild_id: &str, keyword: &str, is_regex: bool) -> Result<bool, Error> {
+        let is_regex = if is_regex { 1 } else { 0 };
+        let result = sqlx::query!("DELETE FROM guild_blocked_keywords WHERE guild_id=? AND keyword=? AND is_regex=?", guild_id, keyword, is_regex)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_message_rows(&self) -> Result<Vec<MessageRow>, Error> {
+        Ok(sqlx::query_as!(MessageRow, "SELECT message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, page, error_count, last_error, last_error_at, quarantined_at, recreate_if_deleted, payload_hash, payload_edited_at, refresh_minutes, next_refresh_at FROM messages")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn get_guild_message_rows(&self, guild_id: &str) -> Result<Vec<MessageRow>, Error> {
+        Ok(sqlx::query_as!(MessageRow, "SELECT message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, page, error_count, last_error, last_error_at, quarantined_at, recreate_if_deleted, payload_hash, payload_edited_at, refresh_minutes, next_refresh_at FROM messages WHERE guild_id=?", guild_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn get_message_row(&self, message_id: &str) -> Result<Option<MessageRow>, Error> {
+        Ok(sqlx::query_as!(MessageRow, "SELECT message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, page, error_count, last_error, last_error_at, quarantined_at, recreate_if_deleted, payload_hash, payload_edited_at, refresh_minutes, next_refresh_at FROM messages WHERE message_id=?", message_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn get_all_message_lists(&self) -> Result<HashMap<String, MessageLists>, Error> {
+        let mut lists: HashMap<String, MessageLists> = HashMap::new();
+        for row in sqlx::query!("SELECT message_id, duty_name FROM message_duties ORDER BY rowid").fetch_all(&self.pool).await? {
+            lists.entry(row.message_id).or_default().duty_names.push(row.duty_name);
+        }
+        for row in sqlx::query!("SELECT message_id, data_center FROM message_data_centers ORDER BY rowid").fetch_all(&self.pool).await? {
+            lists.entry(row.message_id).or_default().data_centers.push(row.data_center);
+        }
+        for row in sqlx::query!("SELECT message_id, category FROM message_categories ORDER BY rowid").fetch_all(&self.pool).await? {
+            lists.entry(row.message_id).or_default().categories.push(row.category);
+        }
+        Ok(lists)
+    }
+
+    async fn get_message_lists(&self, message_id: &str) -> Result<MessageLists, Error> {
+        let duty_names = sqlx::query!("SELECT duty_name FROM message_duties WHERE message_id=? ORDER BY rowid", message_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| x.duty_name).collect();
+        let data_centers = sqlx::query!("SELECT data_center FROM message_data_centers WHERE message_id=? ORDER BY rowid", message_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| x.data_center).collect();
+        let categories = sqlx::query!("SELECT category FROM message_categories WHERE message_id=? ORDER BY rowid", message_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| x.category).collect();
+        Ok(MessageLists { duty_names, data_centers, categories })
+    }
+
+    async fn get_description_filters(&self, message_id: &str) -> Result<Vec<(String, bool)>, Error> {
+        Ok(sqlx::query!("SELECT pattern, is_exclude FROM message_description_filters WHERE message_id=?", message_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| (x.pattern, x.is_exclude != 0)).collect())
+    }
+
+    async fn create_message(&self, message_row: &MessageRow, lists: &MessageLists, include_patterns: &[String], exclude_patterns: &[String]) -> Result<(), Error> {
+        let message_id = &message_row.message_id;
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!("INSERT INTO messages(message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, recreate_if_deleted, refresh_minutes) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            message_row.message_id, message_row.channel_id, message_row.guild_id, message_row.data_center, message_row.duty_name, message_row.allow_statics, message_row.is_news,
+            message_row.filter_case_sensitive, message_row.sort_mode, message_row.party_type, message_row.min_prog_phase, message_row.layout, message_row.overflow, message_row.recreate_if_deleted, message_row.refresh_minutes)
+            .execute(&mut transaction)
+            .await?;
+        for duty_name in &lists.duty_names {
+            sqlx::query!("INSERT INTO message_duties(message_id, duty_name) VALUES(?, ?)", message_id, duty_name)
+                .execute(&mut transaction)
+                .await?;
+        }
+        for category in &lists.categories {
+            sqlx::query!("INSERT INTO message_categories(message_id, category) VALUES(?, ?)", message_id, category)
+                .execute(&mut transaction)
+                .await?;
+        }
+        for data_center in &lists.data_centers {
+            sqlx::query!("INSERT INTO message_data_centers(message_id, data_center) VALUES(?, ?)", message_id, data_center)
+                .execute(&mut transaction)
+                .await?;
+        }
+        for (patterns, is_exclude) in [(include_patterns, 0), (exclude_patterns, 1)] {
+            for pattern in patterns {
+                sqlx::query!("INSERT INTO message_description_filters(message_id, pattern, is_exclude) VALUES(?, ?, ?)", message_id, pattern, is_exclude)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_message(&self, message_id: &str) -> Result<(), Error> {
+        sqlx::query!("DELETE FROM messages WHERE message_id=?", message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_channel_messages(&self, channel_id: &str) -> Result<Vec<String>, Error> {
+        Ok(sqlx::query!("DELETE FROM messages WHERE channel_id=? RETURNING message_id", channel_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| x.message_id).collect())
+    }
+
+    async fn is_guild_message(&self, message_id: &str, guild_id: &str) -> Result<bool, Error> {
+        let row = sqlx::query!("SELECT message_id FROM messages WHERE message_id=? AND guild_id=?", message_id, guild_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn set_message_page(&self, message_id: &str, page: i64) -> Result<(), Error> {
+        sqlx::query!("UPDATE messages SET page=? WHERE message_id=?", page, message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_message_error(&self, message_id: &str, error: &str, at: i64) -> Result<i64, Error> {
+        let row = sqlx::query!("UPDATE messages SET error_count=COALESCE(error_count, 0)+1, last_error=?, last_error_at=? WHERE message_id=? RETURNING error_count",
+            error, at, message_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|x| x.error_count).unwrap_or(0))
+    }
+
+    async fn quarantine_message(&self, message_id: &str, at: i64) -> Result<(), Error> {
+        sqlx::query!("UPDATE messages SET quarantined_at=? WHERE message_id=?", at, message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn clear_message_errors(&self, message_id: &str) -> Result<(), Error> {
+        sqlx::query!("UPDATE messages SET error_count=NULL, last_error=NULL, last_error_at=NULL, quarantined_at=NULL WHERE message_id=?", message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_payload_hash(&self, message_id: &str, payload_hash: &str, edited_at: i64) -> Result<(), Error> {
+        sqlx::query!("UPDATE messages SET payload_hash=?, payload_edited_at=? WHERE message_id=?", payload_hash, edited_at, message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_next_refresh_times(&self, times: &[(String, i64)]) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await?;
+        for (message_id, next_refresh_at) in times {
+            sqlx::query!("UPDATE messages SET next_refresh_at=? WHERE message_id=?", next_refresh_at, message_id)
+                .execute(&mut transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn replace_message_id(&self, message_id: &str, new_message_id: &str) -> Result<(), Error> {
+        // the other tables point at message_id, so the new row goes in before they move over
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!("INSERT INTO messages(message_id, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, recreate_if_deleted, refresh_minutes)
+            SELECT ?, channel_id, guild_id, data_center, duty_name, allow_statics, is_news, filter_case_sensitive, sort_mode, party_type, min_prog_phase, layout, overflow, recreate_if_deleted, refresh_minutes
+            FROM messages WHERE message_id=?", new_message_id, message_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("UPDATE message_duties SET message_id=? WHERE message_id=?", new_message_id, message_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("UPDATE message_data_centers SET message_id=? WHERE message_id=?", new_message_id, message_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("UPDATE message_categories SET message_id=? WHERE message_id=?", new_message_id, message_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("UPDATE message_description_filters SET message_id=? WHERE message_id=?", new_message_id, message_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("UPDATE message_overflow SET message_id=? WHERE message_id=?", new_message_id, message_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("UPDATE message_templates SET message_id=? WHERE message_id=?", new_message_id, message_id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("DELETE FROM messages WHERE message_id=?", message_id)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_overflow_messages(&self, message_id: &str) -> Result<Vec<(i64, String)>, Error> {
+        Ok(sqlx::query!("SELECT position, overflow_message_id FROM message_overflow WHERE message_id=? ORDER BY position", message_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter().map(|x| (x.position, x.overflow_message_id)).collect())
+    }
+
+    async fn save_overflow_message(&self, message_id: &str, position: i64, overflow_message_id: &str) -> Result<(), Error> {
+        sqlx::query!("INSERT OR REPLACE INTO message_overflow(message_id, position, overflow_message_id) VALUES(?, ?, ?)", message_id, position, overflow_message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_overflow_message(&self, message_id: &str, position: i64) -> Result<(), Error> {
+        sqlx::query!("DELETE FROM message_overflow WHERE message_id=? AND position=?", message_id, position)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_overflow_message_by_id(&self, overflow_message_id: &str) -> Result<bool, Error> {
+        let result = sqlx::query!("DELETE FROM message_overflow WHERE overflow_message_id=?", overflow_message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_template(&self, message_id: &str) -> Result<Option<TemplateSource>, Error> {
+        Ok(sqlx::query_as!(TemplateSource, "SELECT title, body, footer, color FROM message_templates WHERE message_id=?", message_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn save_template(&self, message_id: &str, source: &TemplateSource) -> Result<(), Error> {
+        sqlx::query!("INSERT OR REPLACE INTO message_templates(message_id, title, body, footer, color) VALUES(?, ?, ?, ?, ?)",
+            message_id, source.title, source.body, source.footer, source.color)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_template(&self, message_id: &str) -> Result<bool, Error> {
+        let result = sqlx::query!("DELETE FROM message_templates WHERE message_id=?", message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_listings(&self, listings: &[PFListing], fetched_at: i64) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await?;
+        let open_listings = sqlx::query!("SELECT listing_id, filled_count, slot_count, description, last_expires_at FROM listing_history WHERE outcome IS NULL")
+            .fetch_all(&mut transaction)
+            .await?
+            .into_iter()
+            .map(|x| (x.listing_id, history_util::OpenListing { filled_count: x.filled_count, slot_count: x.slot_count, description: x.description, last_expires_at: x.last_expires_at }))
+            .collect::<HashMap<i64, history_util::OpenListing>>();
+        let changes = history_util::get_changes(open_listings, listings, fetched_at);
+
+        for seen in changes.seen {
+            let listing = seen.listing;
+            if seen.is_open {
+                sqlx::query!("UPDATE listing_history SET last_seen_at=?, last_expires_at=?, flags=?, filled_count=?, slot_count=?, description=? WHERE listing_id=?",
+                    fetched_at, listing.expires_at, listing.flags, seen.filled_count, seen.slot_count, listing.description, seen.listing_id)
+                    .execute(&mut transaction)
+                    .await?;
+            } else {
+                // a listing that comes back under the same id picks up where it left off
+                sqlx::query!("INSERT INTO listing_history(listing_id, duty_name, data_center, pf_category, character_name, world, flags, slot_count, filled_count, description, first_seen_at, last_seen_at, last_expires_at)
+                    VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(listing_id) DO UPDATE SET outcome=NULL, ended_at=NULL, flags=excluded.flags, slot_count=excluded.slot_count, filled_count=excluded.filled_count,
+                    description=excluded.description, last_seen_at=excluded.last_seen_at, last_expires_at=excluded.last_expires_at",
+                    seen.listing_id, listing.title, listing.data_center, listing.pf_category, listing.character_name, listing.world, listing.flags, seen.slot_count, seen.filled_count,
+                    listing.description, fetched_at, fetched_at, listing.expires_at)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+            if seen.fill_changed {
+                sqlx::query!("INSERT OR REPLACE INTO listing_fill_events(listing_id, seen_at, filled_count, slot_count) VALUES(?, ?, ?, ?)", seen.listing_id, fetched_at, seen.filled_count, seen.slot_count)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+            if seen.description_changed {
+                sqlx::query!("INSERT OR REPLACE INTO listing_descriptions(listing_id, seen_at, description) VALUES(?, ?, ?)", seen.listing_id, fetched_at, listing.description)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+        }
+
+        for (listing_id, outcome) in changes.ended {
+            let outcome = outcome.to_db_string();
+            sqlx::query!("UPDATE listing_history SET outcome=?, ended_at=? WHERE listing_id=?", outcome, fetched_at, listing_id)
+                .execute(&mut transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn compact_history(&self, delete_before: i64, compact_before: i64) -> Result<u64, Error> {
+        let deleted = sqlx::query!("DELETE FROM listing_history WHERE ended_at < ?", delete_before)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        sqlx::query!("DELETE FROM listing_fill_events AS e WHERE e.listing_id IN (SELECT listing_id FROM listing_history WHERE ended_at < ?)
+            AND e.seen_at > (SELECT MIN(seen_at) FROM listing_fill_events WHERE listing_id=e.listing_id)
+            AND e.seen_at < (SELECT MAX(seen_at) FROM listing_fill_events WHERE listing_id=e.listing_id)", compact_before)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query!("DELETE FROM listing_descriptions AS d WHERE d.listing_id IN (SELECT listing_id FROM listing_history WHERE ended_at < ?)
+            AND d.seen_at > (SELECT MIN(seen_at) FROM listing_descriptions WHERE listing_id=d.listing_id)
+            AND d.seen_at < (SELECT MAX(seen_at) FROM listing_descriptions WHERE listing_id=d.listing_id)", compact_before)
+            .execute(&self.pool)
+            .await?;
+        Ok(deleted)
+    }
+}
@@ -0,0 +1,348 @@
+use crate::settings_util::GuildSettings;
+use crate::template_util::TemplateSource;
+use crate::xiv_util::PFListing;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug)]
+#[allow(dead_code)]
+#[derive(Default)]
+#[derive(sqlx::FromRow)]
+pub struct MessageRow {
+    pub message_id: String,
+    pub channel_id: String,
+    pub data_center: String,
+    pub guild_id: String,
+    pub duty_name: String,
+    pub is_news: Option<i64>,
+    pub allow_statics: Option<i64>,
+    pub filter_case_sensitive: Option<i64>,
+    pub sort_mode: Option<String>,
+    pub party_type: Option<String>,
+    pub min_prog_phase: Option<i64>,
+    pub layout: Option<String>,
+    pub overflow: Option<String>,
+    pub page: Option<i64>,
+    pub error_count: Option<i64>, // failed refreshes in a row
+    pub last_error: Option<String>,
+    pub last_error_at: Option<i64>,
+    pub quarantined_at: Option<i64>,
+    pub recreate_if_deleted: Option<i64>,
+    pub payload_hash: Option<String>, // of what the board last showed, see render_util::get_payload_hash
+    pub payload_edited_at: Option<i64>,
+    pub refresh_minutes: Option<i64>, // unset uses the default, see schedule_util
+    pub next_refresh_at: Option<i64>
+}
+
+// A board's duties, data centers and categories, in the order they were given
+#[derive(Debug)]
+#[derive(Default)]
+pub struct MessageLists {
+    pub duty_names: Vec<String>,
+    pub data_centers: Vec<String>,
+    pub categories: Vec<String>
+}
+
+// Everything the bot keeps in its database. SQLite is always built in, Postgres with the postgres feature.
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    // Brings the schema up to date with the migrations built into this binary
+    async fn migrate(&self) -> Result<(), Error>;
+
+    async fn add_guild(&self, guild_id: &str, guild_name: &str) -> Result<(), Error>;
+    async fn set_guild_name(&self, guild_id: &str, guild_name: &str) -> Result<(), Error>;
+    // Removes a guild and everything it owns, returns its boards' message ids
+    async fn delete_guild(&self, guild_id: &str) -> Result<Vec<String>, Error>;
+    async fn get_guild_settings(&self, guild_id: &str) -> Result<Option<GuildSettings>, Error>;
+    async fn get_all_guild_settings(&self) -> Result<HashMap<String, GuildSettings>, Error>;
+    async fn save_guild_settings(&self, guild_id: &str, settings: &GuildSettings) -> Result<(), Error>;
+
+    // Ordered by character name
+    async fn get_blocked_authors(&self, guild_id: &str) -> Result<Vec<(String, String)>, Error>;
+    async fn add_blocked_author(&self, guild_id: &str, character_name: &str, world: &str) -> Result<(), Error>;
+    // Case insensitive, returns whether anything was removed
+    async fn remove_blocked_author(&self, guild_id: &str, character_name: &str, world: &str) -> Result<bool, Error>;
+    // Ordered by keyword, with whether it's a regex
+    async fn get_blocked_keywords(&self, guild_id: &str) -> Result<Vec<(String, bool)>, Error>;
+    async fn add_blocked_keyword(&self, guild_id: &str, keyword: &str, is_regex: bool) -> Result<(), Error>;
+    // A keyword can be on the blocklist both as text and as a regex, so is_regex picks which one goes
+    async fn remove_blocked_keyword(&self, guild_id: &str, keyword: &str, is_regex: bool) -> Result<bool, Error>;
+
+    async fn get_message_rows(&self) -> Result<Vec<MessageRow>, Error>;
+    async fn get_guild_message_rows(&self, guild_id: &str) -> Result<Vec<MessageRow>, Error>;
+    async fn get_message_row(&self, message_id: &str) -> Result<Option<MessageRow>, Error>;
+    // Per message_id
+    async fn get_all_message_lists(&self) -> Result<HashMap<String, MessageLists>, Error>;
+    async fn get_message_lists(&self, message_id: &str) -> Result<MessageLists, Error>;
+    // Patterns with whether they exclude
+    async fn get_description_filters(&self, message_id: &str) -> Result<Vec<(String, bool)>, Error>;
+    async fn create_message(&self, message_row: &MessageRow, lists: &MessageLists, include_patterns: &[String], exclude_patterns: &[String]) -> Result<(), Error>;
+    async fn delete_message(&self, message_id: &str) -> Result<(), Error>;
+    // Removes the boards in a channel, returns their message ids
+    async fn delete_channel_messages(&self, channel_id: &str) -> Result<Vec<String>, Error>;
+    async fn is_guild_message(&self, message_id: &str, guild_id: &str) -> Result<bool, Error>;
+    async fn set_message_page(&self, message_id: &str, page: i64) -> Result<(), Error>;
+    // Returns how many refreshes in a row have failed, this one included
+    async fn record_message_error(&self, message_id: &str, error: &str, at: i64) -> Result<i64, Error>;
+    async fn quarantine_message(&self, message_id: &str, at: i64) -> Result<(), Error>;
+    async fn clear_message_errors(&self, message_id: &str) -> Result<(), Error>;
+    async fn set_payload_hash(&self, message_id: &str, payload_hash: &str, edited_at: i64) -> Result<(), Error>;
+    // Message ids with when each is next due, in one go for a whole refresh
+    async fn set_next_refresh_times(&self, times: &[(String, i64)]) -> Result<(), Error>;
+    // For a board posted again, keeps everything it owns and starts it on its first page with no errors
+    async fn replace_message_id(&self, message_id: &str, new_message_id: &str) -> Result<(), Error>;
+
+    // Positions with their message ids, ordered by position
+    async fn get_overflow_messages(&self, message_id: &str) -> Result<Vec<(i64, String)>, Error>;
+    async fn save_overflow_message(&self, message_id: &str, position: i64, overflow_message_id: &str) -> Result<(), Error>;
+    async fn delete_overflow_message(&self, message_id: &str, position: i64) -> Result<(), Error>;
+    // For extra messages deleted in Discord, returns whether it was one
+    async fn delete_overflow_message_by_id(&self, overflow_message_id: &str) -> Result<bool, Error>;
+
+    async fn get_template(&self, message_id: &str) -> Result<Option<TemplateSource>, Error>;
+    async fn save_template(&self, message_id: &str, source: &TemplateSource) -> Result<(), Error>;
+    async fn delete_template(&self, message_id: &str) -> Result<bool, Error>;
+
+    // See history_util, which decides what gets written
+    async fn record_listings(&self, listings: &[PFListing], fetched_at: i64) -> Result<(), Error>;
+    async fn compact_history(&self, delete_before: i64, compact_before: i64) -> Result<u64, Error>;
+}
+
+// Descriptions of the migrations still to apply
+pub fn get_pending_migrations(migrator: &sqlx::migrate::Migrator, applied: &[sqlx::migrate::AppliedMigration]) -> Result<Vec<String>, Error> {
+    // running old code against a newer schema would fail at the first query that touches what changed
+    if let Some(unknown) = applied.iter().find(|x| !migrator.iter().any(|y| y.version == x.version)) {
+        return Err(format!("The database has migration {} applied, which this build doesn't know about. \
+            It was last used by a newer version of the bot. Update the bot, or restore a backup made before that version ran.", unknown.version).into());
+    }
+    Ok(migrator.iter().filter(|x| !applied.iter().any(|y| y.version == x.version)).map(|x| x.description.to_string()).collect())
+}
+
+// DATABASE_URL picks the backend when the bot runs. Anything but a postgres:// url keeps the data in database.sqlite.
+pub async fn connect() -> Result<Arc<dyn Storage>, Error> {
+    let url = std::env::var("DATABASE_URL").unwrap_or_default();
+    if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+        return connect_postgres(&url).await;
+    }
+    Ok(Arc::new(crate::sqlite_util::SqliteStorage::connect("database.sqlite").await?))
+}
+
+#[cfg(feature = "postgres")]
+async fn connect_postgres(url: &str) -> Result<Arc<dyn Storage>, Error> {
+    Ok(Arc::new(crate::postgres_util::PostgresStorage::connect(url).await?))
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn connect_postgres(_url: &str) -> Result<Arc<dyn Storage>, Error> {
+    Err("DATABASE_URL is a Postgres url, but this build doesn't have the postgres feature. Build with --features postgres.".into())
+}
+
+// Runs the same checks against each backend. The Postgres ones need the postgres feature and TEST_POSTGRES_URL,
+// and work in a schema of their own that's dropped afterwards.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xiv_util::Slot;
+
+    const GUILD_ID: &str = "100";
+
+    fn get_unique_name(prefix: &str) -> String {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+        format!("{}_{}_{}", prefix, std::process::id(), nanos)
+    }
+
+    fn get_message_row(message_id: &str) -> MessageRow {
+        MessageRow {
+            message_id: message_id.to_string(),
+            channel_id: "200".to_string(),
+            guild_id: GUILD_ID.to_string(),
+            data_center: "Primal".to_string(),
+            duty_name: "Dragonsong's Reprise (Ultimate)".to_string(),
+            layout: Some("compact".to_string()),
+            recreate_if_deleted: Some(1),
+            refresh_minutes: Some(15),
+            ..MessageRow::default()
+        }
+    }
+
+    fn get_lists() -> MessageLists {
+        MessageLists {
+            duty_names: vec!["The Weapon's Refrain (Ultimate)".to_string(), "Dragonsong's Reprise (Ultimate)".to_string(), "The Epic of Alexander (Ultimate)".to_string()],
+            data_centers: vec!["Primal".to_string(), "Aether".to_string()],
+            categories: vec!["HighEndDuty".to_string()]
+        }
+    }
+
+    fn get_listing(id: u64, filled: usize, expires_at: i64) -> PFListing {
+        PFListing {
+            id,
+            title: "Dragonsong's Reprise (Ultimate)".to_string(),
+            author: "Alpha Beta @ Lamia".to_string(),
+            character_name: "Alpha Beta".to_string(),
+            world: "Lamia".to_string(),
+            flags: "[Practice]".to_string(),
+            description: "P6 prog".to_string(),
+            slots: (0..8).map(|i| Slot { available_jobs: Vec::new(), filled: i < filled }).collect(),
+            last_updated: "now".to_string(),
+            expires_in: "in an hour".to_string(),
+            last_updated_at: 0,
+            expires_at,
+            min_ilvl: "0".to_string(),
+            data_center: "Primal".to_string(),
+            pf_category: "HighEndDuty".to_string(),
+            prog: Default::default(),
+            is_new: false
+        }
+    }
+
+    async fn check_messages(storage: &dyn Storage) {
+        storage.add_guild(GUILD_ID, "Test guild").await.unwrap();
+        let lists = get_lists();
+        storage.create_message(&get_message_row("1"), &lists, &["p[5-7]".to_string()], &["static".to_string()]).await.unwrap();
+
+        let row = storage.get_message_row("1").await.unwrap().unwrap();
+        assert_eq!(row.channel_id, "200");
+        assert_eq!(row.layout.as_deref(), Some("compact"));
+        assert_eq!(row.recreate_if_deleted, Some(1));
+        assert_eq!(row.refresh_minutes, Some(15));
+        assert!(storage.is_guild_message("1", GUILD_ID).await.unwrap());
+        assert!(!storage.is_guild_message("1", "999").await.unwrap());
+        storage.add_guild("999", "Other guild").await.unwrap();
+        storage.create_message(&MessageRow { message_id: "3".to_string(), guild_id: "999".to_string(), ..get_message_row("3") }, &lists, &[], &[]).await.unwrap();
+        assert_eq!(storage.get_guild_message_rows(GUILD_ID).await.unwrap().iter().map(|x| x.message_id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        assert_eq!(storage.get_message_rows().await.unwrap().len(), 2);
+        assert_eq!(storage.delete_guild("999").await.unwrap(), vec!["3".to_string()]);
+
+        // lists come back in the order they were given, not sorted
+        let stored = storage.get_message_lists("1").await.unwrap();
+        assert_eq!(stored.duty_names, lists.duty_names);
+        assert_eq!(stored.data_centers, lists.data_centers);
+        assert_eq!(stored.categories, lists.categories);
+        assert_eq!(storage.get_all_message_lists().await.unwrap()["1"].duty_names, lists.duty_names);
+        assert_eq!(storage.get_description_filters("1").await.unwrap(), vec![("p[5-7]".to_string(), false), ("static".to_string(), true)]);
+    }
+
+    async fn check_replace_message_id(storage: &dyn Storage) {
+        storage.set_message_page("1", 2).await.unwrap();
+        storage.record_message_error("1", "Missing Access", 1000).await.unwrap();
+        storage.save_overflow_message("1", 1, "11").await.unwrap();
+        storage.save_template("1", &TemplateSource { title: "t".to_string(), body: "{{#listings}}{{author}}{{/listings}}".to_string(), footer: "".to_string(), color: "".to_string() }).await.unwrap();
+
+        storage.replace_message_id("1", "2").await.unwrap();
+        assert!(storage.get_message_row("1").await.unwrap().is_none());
+        let row = storage.get_message_row("2").await.unwrap().unwrap();
+        assert_eq!(row.page, None);
+        assert_eq!(row.error_count.unwrap_or(0), 0);
+        assert_eq!(row.refresh_minutes, Some(15));
+        assert_eq!(storage.get_message_lists("2").await.unwrap().duty_names, get_lists().duty_names);
+        assert_eq!(storage.get_description_filters("2").await.unwrap().len(), 2);
+        assert_eq!(storage.get_overflow_messages("2").await.unwrap(), vec![(1, "11".to_string())]);
+        assert!(storage.get_template("2").await.unwrap().is_some());
+        assert!(storage.get_template("1").await.unwrap().is_none());
+    }
+
+    async fn check_templates(storage: &dyn Storage) {
+        let mut source = storage.get_template("2").await.unwrap().unwrap();
+        assert_eq!(source.title, "t");
+        source.color = "#5865F2".to_string();
+        storage.save_template("2", &source).await.unwrap();
+        assert_eq!(storage.get_template("2").await.unwrap().unwrap().color, "#5865F2");
+        assert!(storage.delete_template("2").await.unwrap());
+        assert!(!storage.delete_template("2").await.unwrap());
+        assert!(storage.get_template("2").await.unwrap().is_none());
+    }
+
+    async fn check_blocklists(storage: &dyn Storage) {
+        storage.add_blocked_author(GUILD_ID, "Zed Zulu", "Lamia").await.unwrap();
+        storage.add_blocked_author(GUILD_ID, "Alpha Beta", "Lamia").await.unwrap();
+        storage.add_blocked_author(GUILD_ID, "Alpha Beta", "Lamia").await.unwrap();
+        assert_eq!(storage.get_blocked_authors(GUILD_ID).await.unwrap(),
+            vec![("Alpha Beta".to_string(), "Lamia".to_string()), ("Zed Zulu".to_string(), "Lamia".to_string())]);
+        assert!(storage.remove_blocked_author(GUILD_ID, "alpha beta", "LAMIA").await.unwrap());
+        assert!(!storage.remove_blocked_author(GUILD_ID, "alpha beta", "lamia").await.unwrap());
+        assert_eq!(storage.get_blocked_authors(GUILD_ID).await.unwrap().len(), 1);
+
+        storage.add_blocked_keyword(GUILD_ID, "gamma", false).await.unwrap();
+        storage.add_blocked_keyword(GUILD_ID, "alpha", true).await.unwrap();
+        assert_eq!(storage.get_blocked_keywords(GUILD_ID).await.unwrap(), vec![("alpha".to_string(), true), ("gamma".to_string(), false)]);
+        assert!(storage.remove_blocked_keyword(GUILD_ID, "gamma", false).await.unwrap());
+        assert!(!storage.remove_blocked_keyword(GUILD_ID, "gamma", false).await.unwrap());
+        assert_eq!(storage.get_blocked_keywords(GUILD_ID).await.unwrap(), vec![("alpha".to_string(), true)]);
+        // the same text as a literal and as a regex are separate entries
+        storage.add_blocked_keyword(GUILD_ID, "alpha", false).await.unwrap();
+        assert!(!storage.remove_blocked_keyword(GUILD_ID, "gamma", true).await.unwrap());
+        assert!(storage.remove_blocked_keyword(GUILD_ID, "alpha", true).await.unwrap());
+        assert_eq!(storage.get_blocked_keywords(GUILD_ID).await.unwrap(), vec![("alpha".to_string(), false)]);
+    }
+
+    async fn check_history(storage: &dyn Storage) {
+        let day = 24 * 60 * 60;
+        storage.record_listings(&[get_listing(1, 2, 10 * day), get_listing(2, 7, 10 * day)], 0).await.unwrap();
+        storage.record_listings(&[get_listing(1, 3, 10 * day), get_listing(2, 7, 10 * day)], 60).await.unwrap();
+        // listing 2 is gone, so it ended
+        storage.record_listings(&[get_listing(1, 4, 10 * day)], 120).await.unwrap();
+
+        // nothing ended before the cutoff yet
+        assert_eq!(storage.compact_history(60, 60).await.unwrap(), 0);
+        assert_eq!(storage.compact_history(121, 121).await.unwrap(), 1);
+        // listing 1 is still open and never deleted
+        assert_eq!(storage.compact_history(day, day).await.unwrap(), 0);
+
+        // a listing that comes back under its id is open again
+        storage.record_listings(&[get_listing(2, 1, 10 * day)], 180).await.unwrap();
+        storage.record_listings(&[get_listing(3, 1, 10 * day)], 240).await.unwrap();
+        assert_eq!(storage.compact_history(day, day).await.unwrap(), 2);
+    }
+
+    async fn check_guild_deletion(storage: &dyn Storage) {
+        assert_eq!(storage.delete_guild(GUILD_ID).await.unwrap(), vec!["2".to_string()]);
+        assert!(storage.get_message_rows().await.unwrap().is_empty());
+        assert!(storage.get_blocked_authors(GUILD_ID).await.unwrap().is_empty());
+        assert!(storage.get_blocked_keywords(GUILD_ID).await.unwrap().is_empty());
+    }
+
+    async fn check_storage(storage: &dyn Storage) {
+        storage.migrate().await.unwrap();
+        check_messages(storage).await;
+        check_replace_message_id(storage).await;
+        check_templates(storage).await;
+        check_blocklists(storage).await;
+        check_history(storage).await;
+        check_guild_deletion(storage).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_storage() {
+        let path = std::env::temp_dir().join(format!("{}.sqlite", get_unique_name("trappingway_test")));
+        let storage = crate::sqlite_util::SqliteStorage::connect(path.to_str().unwrap()).await.unwrap();
+        check_storage(&storage).await;
+        drop(storage);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn postgres_storage() {
+        let url = match std::env::var("TEST_POSTGRES_URL") {
+            Ok(x) => x,
+            Err(_) => {
+                println!("TEST_POSTGRES_URL isn't set, skipping the Postgres storage tests");
+                return;
+            }
+        };
+        let schema = get_unique_name("trappingway_test");
+        let admin = sqlx::PgPool::connect(&url).await.unwrap();
+        sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&admin).await.unwrap();
+
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let storage = crate::postgres_util::PostgresStorage::connect(&format!("{}{}options[search_path]={}", url, separator, schema)).await.unwrap();
+        // a failed check still drops the schema
+        let result = tokio::spawn(async move { check_storage(&storage).await }).await;
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema)).execute(&admin).await.unwrap();
+        if let Err(e) = result {
+            std::panic::resume_unwind(e.into_panic());
+        }
+    }
+}
@@ -0,0 +1,269 @@
+// Board templates. Text with {{placeholders}}, one {{#listings}}...{{/listings}} loop in the body, and
+// {{?name}}...{{/name}} sections that only show when the placeholder isn't empty.
+
+pub const MAX_TITLE_LENGTH: usize = 200;
+pub const MAX_BODY_LENGTH: usize = 2000;
+pub const MAX_FOOTER_LENGTH: usize = 500;
+
+// Placeholders anywhere in a template
+pub const BOARD_PLACEHOLDERS: &[&str] = &["title", "duties", "data_centers", "count"];
+// Placeholders inside the listings loop
+pub const LISTING_PLACEHOLDERS: &[&str] = &["author", "character", "world", "data_center", "duty", "category", "roles", "open_roles",
+    "filled", "slots", "flags", "prog", "description", "updated", "expires", "min_ilvl", "badges"];
+
+// What's stored in message_templates and edited in the modal
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(sqlx::FromRow)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TemplateSource {
+    pub title: String,
+    pub body: String,
+    pub footer: String,
+    pub color: String
+}
+
+// Built-in templates, also the starting point when editing
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(poise::ChoiceParameter)]
+pub enum TemplatePreset {
+    #[name = "Classic"]
+    Classic,
+    #[name = "Compact"]
+    Compact,
+    #[name = "Minimal"]
+    Minimal,
+    #[name = "Prog"]
+    Prog
+}
+
+impl TemplatePreset {
+    pub fn get_source(&self) -> TemplateSource {
+        let (title, body, footer, color) = match self {
+            TemplatePreset::Classic => ("{{title}}",
+                "{{#listings}}{{?badges}}{{badges}} {{/badges}}**{{author}}** {{roles}}\n{{?flags}}*{{flags}}*\n{{/flags}}{{description}}\n{{updated}} · {{expires}}\n\n{{/listings}}", "", ""),
+            TemplatePreset::Compact => ("{{title}} ({{count}})",
+                "{{#listings}}{{open_roles}} {{?badges}}{{badges}} {{/badges}}**{{author}}** ({{filled}}/{{slots}}) {{description}} · {{updated}}\n{{/listings}}", "", ""),
+            TemplatePreset::Minimal => ("{{duties}}",
+                "{{#listings}}• {{author}}: {{description}}\n{{/listings}}", "{{data_centers}}", "#5865F2"),
+            TemplatePreset::Prog => ("{{title}}",
+                "{{#listings}}{{?prog}}**{{prog}}** {{/prog}}{{author}} ({{filled}}/{{slots}}) · {{expires}}\n> {{description}}\n{{/listings}}", "", "")
+        };
+        TemplateSource { title: title.to_string(), body: body.to_string(), footer: footer.to_string(), color: color.to_string() }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Value(String),
+    IfNotEmpty(String, Vec<Node>),
+    Listings(Vec<Node>)
+}
+
+#[derive(Debug)]
+pub struct BoardTemplate {
+    title: Vec<Node>,
+    before_listings: Vec<Node>,
+    listing: Vec<Node>,
+    after_listings: Vec<Node>,
+    footer: Vec<Node>,
+    pub color: Option<u32>
+}
+
+enum Token {
+    Text(String),
+    Value(String),
+    Open(char, String),
+    Close(String)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..].find("}}").ok_or_else(|| format!("`{}` is never closed with }}}}.", &rest[start..].chars().take(20).collect::<String>()))?;
+        let tag = rest[start + 2..start + end].trim();
+        tokens.push(match tag.chars().next() {
+            Some(kind @ ('#' | '?')) => Token::Open(kind, tag[1..].trim().to_string()),
+            Some('/') => Token::Close(tag[1..].trim().to_string()),
+            Some(_) => Token::Value(tag.to_string()),
+            None => return Err("Empty {{}} in template.".to_string())
+        });
+        rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+fn check_placeholder(name: &str, in_listings: bool) -> Result<(), String> {
+    if BOARD_PLACEHOLDERS.contains(&name) || (in_listings && LISTING_PLACEHOLDERS.contains(&name)) {
+        Ok(())
+    } else if LISTING_PLACEHOLDERS.contains(&name) {
+        Err(format!("{{{{{}}}}} can only be used inside {{{{#listings}}}}...{{{{/listings}}}}.", name))
+    } else {
+        Err(format!("Unknown placeholder {{{{{}}}}}.", name))
+    }
+}
+
+// Builds the node tree, checking names and that sections close in order
+fn parse_nodes(tokens: &mut std::iter::Peekable<std::vec::IntoIter<Token>>, closing: Option<&str>, in_listings: bool, allow_listings: bool) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text)),
+            Token::Value(name) => {
+                check_placeholder(&name, in_listings)?;
+                nodes.push(Node::Value(name));
+            }
+            Token::Open('#', name) => {
+                if name != "listings" {
+                    return Err(format!("Unknown loop {{{{#{}}}}}, only {{{{#listings}}}} is supported.", name));
+                }
+                if !allow_listings || in_listings || closing.is_some() {
+                    return Err("{{#listings}} can only be used once, at the top level of the body.".to_string());
+                }
+                nodes.push(Node::Listings(parse_nodes(tokens, Some("listings"), true, false)?));
+            }
+            Token::Open(_, name) => {
+                check_placeholder(&name, in_listings)?;
+                let body = parse_nodes(tokens, Some(&name), in_listings, false)?;
+                nodes.push(Node::IfNotEmpty(name, body));
+            }
+            Token::Close(name) => {
+                return match closing {
+                    Some(x) if x == name => Ok(nodes),
+                    Some(x) => Err(format!("Expected {{{{/{}}}}} but found {{{{/{}}}}}.", x, name)),
+                    None => Err(format!("{{{{/{}}}}} doesn't close anything.", name))
+                };
+            }
+        }
+    }
+    match closing {
+        Some(x) => Err(format!("{{{{{}{}}}}} is never closed with {{{{/{}}}}}.", if x == "listings" {"#"} else {"?"}, x, x)),
+        None => Ok(nodes)
+    }
+}
+
+fn parse_part(text: &str, max_length: usize, part_name: &str, allow_listings: bool) -> Result<Vec<Node>, String> {
+    if text.chars().count() > max_length {
+        return Err(format!("The {} is longer than {} characters.", part_name, max_length));
+    }
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+    parse_nodes(&mut tokens, None, false, allow_listings)
+}
+
+pub fn parse_color(input: &str) -> Result<Option<u32>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let hex = input.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("Color `{}` should look like #5865F2.", input));
+    }
+    u32::from_str_radix(hex, 16).map(Some).map_err(|_| format!("Color `{}` should look like #5865F2.", input))
+}
+
+impl BoardTemplate {
+    pub fn parse(source: &TemplateSource) -> Result<BoardTemplate, String> {
+        let title = parse_part(&source.title, MAX_TITLE_LENGTH, "title", false)?;
+        let body = parse_part(&source.body, MAX_BODY_LENGTH, "body", true)?;
+        let footer = parse_part(&source.footer, MAX_FOOTER_LENGTH, "footer", false)?;
+        let color = parse_color(&source.color)?;
+
+        let mut before_listings = Vec::new();
+        let mut listing = Vec::new();
+        let mut after_listings = Vec::new();
+        let mut seen_listings = false;
+        for node in body {
+            match node {
+                Node::Listings(_) if seen_listings => {
+                    return Err("The body can only have one {{#listings}}...{{/listings}} loop.".to_string());
+                }
+                Node::Listings(nodes) => {
+                    listing = nodes;
+                    seen_listings = true;
+                }
+                x if seen_listings => after_listings.push(x),
+                x => before_listings.push(x)
+            }
+        }
+        if !seen_listings {
+            return Err("The body needs a {{#listings}}...{{/listings}} loop.".to_string());
+        }
+        Ok(BoardTemplate { title, before_listings, listing, after_listings, footer, color })
+    }
+
+    pub fn render_title(&self, get_value: &dyn Fn(&str) -> String) -> String {
+        render_nodes(&self.title, get_value)
+    }
+
+    pub fn render_footer(&self, get_value: &dyn Fn(&str) -> String) -> String {
+        render_nodes(&self.footer, get_value)
+    }
+
+    pub fn render_before_listings(&self, get_value: &dyn Fn(&str) -> String) -> String {
+        render_nodes(&self.before_listings, get_value)
+    }
+
+    pub fn render_listing(&self, get_value: &dyn Fn(&str) -> String) -> String {
+        render_nodes(&self.listing, get_value)
+    }
+
+    pub fn render_after_listings(&self, get_value: &dyn Fn(&str) -> String) -> String {
+        render_nodes(&self.after_listings, get_value)
+    }
+}
+
+fn render_nodes(nodes: &[Node], get_value: &dyn Fn(&str) -> String) -> String {
+    let mut result = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(text) => result.push_str(text),
+            Node::Value(name) => result.push_str(&get_value(name)),
+            Node::IfNotEmpty(name, body) => {
+                if !get_value(name).is_empty() {
+                    result.push_str(&render_nodes(body, get_value));
+                }
+            }
+            // parse only leaves loops at the top level of the body, which is split up
+            Node::Listings(_) => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_source(body: &str) -> TemplateSource {
+        TemplateSource { title: "".to_string(), body: body.to_string(), footer: "".to_string(), color: "".to_string() }
+    }
+
+    #[test]
+    fn splits_body_around_listings() {
+        let template = BoardTemplate::parse(&get_source("Top\n{{#listings}}{{author}}\n{{/listings}}Bottom")).unwrap();
+        let get_value = |name: &str| if name == "author" { "A".to_string() } else { "".to_string() };
+        assert_eq!(template.render_before_listings(&get_value), "Top\n");
+        assert_eq!(template.render_listing(&get_value), "A\n");
+        assert_eq!(template.render_after_listings(&get_value), "Bottom");
+    }
+
+    #[test]
+    fn rejects_missing_listings() {
+        assert!(BoardTemplate::parse(&get_source("{{count}} listings")).is_err());
+    }
+
+    #[test]
+    fn rejects_second_listings() {
+        assert!(BoardTemplate::parse(&get_source("{{#listings}}{{author}}{{/listings}}{{#listings}}{{duty}}{{/listings}}")).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::str::FromStr;
 use std::fmt;
+use crate::prog_util::ProgTag;
 
 #[derive(Debug)]
 #[derive(PartialEq)]
@@ -38,25 +39,62 @@ pub enum Job {
 
 #[derive(Debug)]
 #[derive(PartialEq)]
+#[derive(Clone, Copy)]
 pub enum Role {
     Tank,
     DPS,
     Healer
 }
 
+// What a party is going for, from the colored flag xivpf shows first
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub enum Objective {
+    Practice,
+    Loot,
+    Completion
+}
+
+impl Objective {
+    pub fn get_emoji(&self) -> &'static str {
+        match self {
+            Objective::Practice => "🟢",
+            Objective::Loot => "🟡",
+            Objective::Completion => "🔵"
+        }
+    }
+
+    // the same colors xivpf uses for the flags
+    pub fn get_color(&self) -> u32 {
+        match self {
+            Objective::Practice => 0x57f287,
+            Objective::Loot => 0xfee75c,
+            Objective::Completion => 0x3498db
+        }
+    }
+}
+
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct PFListing {
+    pub id: u64,
     pub title: String,
     pub author: String,
+    pub character_name: String, // author split up and unsanitized
+    pub world: String,
     pub flags: String,
     pub description: String,
     pub slots: Vec<Slot>,
     pub last_updated: String,
     pub expires_in: String,
+    pub last_updated_at: i64, // unix seconds, worked out from the relative times above when scraped
+    pub expires_at: i64,
     pub min_ilvl: String,
     pub data_center: String,
-    pub pf_category: String
+    pub pf_category: String,
+    pub prog: ProgTag,
+    pub is_new: bool // not in the fetch before this one
 }
 
 #[derive(Debug)]
@@ -66,6 +104,33 @@ pub struct Slot {
     pub filled: bool,
 }
 
+impl PFListing {
+    pub fn get_filled_count(&self) -> usize {
+        self.slots.iter().filter(|x| x.filled).count()
+    }
+
+    // Open slots that a job of this role could take
+    pub fn get_open_count_for_role(&self, role: Role) -> usize {
+        self.slots.iter().filter(|x| !x.filled && x.available_jobs.iter().any(|y| y.get_role() == role)).count()
+    }
+
+    pub fn get_min_ilvl(&self) -> u32 {
+        self.min_ilvl.trim().parse::<u32>().unwrap_or(0)
+    }
+
+    pub fn get_objective(&self) -> Option<Objective> {
+        if self.flags.contains("[Practice]") {
+            Some(Objective::Practice)
+        } else if self.flags.contains("[Loot]") {
+            Some(Objective::Loot)
+        } else if self.flags.contains("[Duty Completion]") {
+            Some(Objective::Completion)
+        } else {
+            None
+        }
+    }
+}
+
 #[allow(dead_code)]
 impl Slot {   
     pub fn to_string(&self) -> String {
@@ -240,4 +305,108 @@ pub fn get_color_from_duty(duty_name: &str) -> u32 {
         "Dragonsong's Reprise (Ultimate)" =>  0xf12916,
         _ =>  0xf0a057
     }
-}
\ No newline at end of file
+}
+pub fn get_color_from_duties(duty_names: &[String]) -> u32 {
+    // boards spanning duties only keep a duty's color if they all share it
+    let colors = duty_names.iter().map(|x| get_color_from_duty(x)).collect::<Vec<u32>>();
+    match colors.first() {
+        Some(first) if colors.iter().all(|x| x == first) => *first,
+        _ => get_color_from_duty("")
+    }
+}
+
+pub const DATA_CENTERS: [&str; 11] = ["Crystal", "Aether", "Primal", "Dynamis", "Elemental", "Gaia", "Mana", "Meteor", "Chaos", "Light", "Materia"];
+
+pub const REGIONS: [&str; 4] = ["North America", "Europe", "Japan", "Oceania"];
+
+pub fn get_region_data_centers(region: &str) -> Option<Vec<&'static str>> {
+    match region.to_lowercase().as_str() {
+        "north america" | "na" => Some(vec!["Aether", "Crystal", "Dynamis", "Primal"]),
+        "europe" | "eu" => Some(vec!["Chaos", "Light"]),
+        "japan" | "jp" => Some(vec!["Elemental", "Gaia", "Mana", "Meteor"]),
+        "oceania" | "oce" => Some(vec!["Materia"]),
+        _ => None
+    }
+}
+
+// Name of the region if the data centers cover exactly one whole region
+pub fn get_region_name(data_centers: &[String]) -> Option<&'static str> {
+    REGIONS.iter().find(|region| {
+        let region_dcs = get_region_data_centers(region).unwrap();
+        region_dcs.len() == data_centers.len() && region_dcs.iter().all(|x| data_centers.iter().any(|y| y == x))
+    }).copied()
+}
+
+// data-pf-category values on xivpf and their in game names
+pub const PF_CATEGORIES: [(&str, &str); 15] = [
+    ("DutyRoulette", "Duty Roulette"),
+    ("Dungeons", "Dungeons"),
+    ("Guildhests", "Guildhests"),
+    ("Trials", "Trials"),
+    ("Raids", "Raids"),
+    ("HighEndDuty", "High-end Duty"),
+    ("Pvp", "PvP"),
+    ("QuestBattles", "Quest Battles"),
+    ("Fates", "FATEs"),
+    ("TreasureHunt", "Treasure Hunt"),
+    ("TheHunt", "The Hunt"),
+    ("GatheringForays", "Gathering Forays"),
+    ("DeepDungeons", "Deep Dungeons"),
+    ("AdventuringForays", "Field Operations"),
+    ("None", "None")
+];
+
+// Accepts either the xivpf name or the in game name
+pub fn parse_category(input: &str) -> Option<&'static str> {
+    PF_CATEGORIES.iter()
+        .find(|(category, display_name)| category.eq_ignore_ascii_case(input) || display_name.eq_ignore_ascii_case(input))
+        .map(|(category, _)| *category)
+}
+
+pub fn get_category_display_name(category: &str) -> &str {
+    match PF_CATEGORIES.iter().find(|(x, _)| *x == category) {
+        Some((_, display_name)) => display_name,
+        None => category
+    }
+}
+
+pub fn get_color_from_category(category: &str) -> u32 {
+    match category {
+        "HighEndDuty" => 0xf12916,
+        "Raids" => 0xfcaa00,
+        "Trials" => 0x008bfc,
+        "TheHunt" => 0x8a5a2b,
+        "DeepDungeons" => 0x6a3d9a,
+        "AdventuringForays" | "GatheringForays" => 0x2e8b57,
+        "TreasureHunt" => 0xfce100,
+        _ => get_color_from_duty("")
+    }
+}
+
+pub fn get_unix_time() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|x| x.as_secs() as i64).unwrap_or(0)
+}
+
+// FNV-1a, which unlike DefaultHasher stays the same across Rust versions, for hashes that are saved or spread things out
+pub fn get_stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, x| (hash ^ *x as u64).wrapping_mul(0x100000001b3))
+}
+
+// Minutes in one of xivpf's relative times, e.g. "now", "a minute ago", "in 38 minutes", "an hour ago"
+pub fn parse_relative_minutes(text: &str) -> i32 {
+    let amount = if text.contains("a minute") || text.contains("an hour") || text.contains("a day") {
+        1
+    } else {
+        text.chars().filter(|c| c.is_digit(10)).collect::<String>().parse::<i32>().unwrap_or(0)
+    };
+
+    if text.contains("day") {
+        amount * 60 * 24
+    } else if text.contains("hour") {
+        amount * 60
+    } else if text.contains("minute") {
+        amount
+    } else {
+        0
+    }
+}